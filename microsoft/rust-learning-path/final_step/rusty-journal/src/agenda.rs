@@ -0,0 +1,326 @@
+//! Open tasks by due date: `agenda` sorts them into sections from overdue to
+//! later, and `calendar` draws a month with the number of tasks due each day.
+//!
+//! Days are local days, the same as the overdue marker of `Display for Task`:
+//! "today" is today in the local time zone, and weeks run Monday to Sunday.
+
+use crate::config;
+use crate::dates;
+use crate::output::{self, Columns, Entry, OutputFormat};
+use crate::storage::Storage;
+use crate::table::{self, Cell, Colour, Table, Terminal};
+use crate::tasks::{Journal, Task};
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Result;
+
+/// The sections of the agenda, in the order they're shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Overdue,
+    Today,
+    Tomorrow,
+    ThisWeek,
+    Later,
+    NoDate,
+}
+
+const SECTIONS: [Section; 6] = [
+    Section::Overdue,
+    Section::Today,
+    Section::Tomorrow,
+    Section::ThisWeek,
+    Section::Later,
+    Section::NoDate,
+];
+
+impl Section {
+    fn of(due: Option<NaiveDate>, today: NaiveDate) -> Section {
+        let Some(due) = due else {
+            return Section::NoDate;
+        };
+        let sunday = today + Days::new((6 - today.weekday().num_days_from_monday()).into());
+        match (due - today).num_days() {
+            ..0 => Section::Overdue,
+            0 => Section::Today,
+            1 => Section::Tomorrow,
+            _ if due <= sunday => Section::ThisWeek,
+            _ => Section::Later,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Section::Overdue => "Overdue",
+            Section::Today => "Today",
+            Section::Tomorrow => "Tomorrow",
+            Section::ThisWeek => "This week",
+            Section::Later => "Later",
+            Section::NoDate => "No date",
+        }
+    }
+
+    /// The section's name in the machine formats.
+    fn key(self) -> &'static str {
+        match self {
+            Section::Overdue => "overdue",
+            Section::Today => "today",
+            Section::Tomorrow => "tomorrow",
+            Section::ThisWeek => "this_week",
+            Section::Later => "later",
+            Section::NoDate => "no_date",
+        }
+    }
+}
+
+/// Every open task in its section, earliest due first and then by urgency.
+fn sections(journal: &Journal, today: NaiveDate) -> Vec<(Section, Vec<&Task>)> {
+    let mut sections: Vec<(Section, Vec<&Task>)> = SECTIONS.iter().map(|&section| (section, Vec::new())).collect();
+    for task in journal.tasks.iter().filter(|task| !task.is_completed()) {
+        let section = Section::of(task.due, today);
+        sections[SECTIONS.iter().position(|&s| s == section).expect("every section is listed")]
+            .1
+            .push(task);
+    }
+    let now = chrono::Utc::now();
+    for (_, tasks) in &mut sections {
+        tasks.sort_by_cached_key(|task| (task.due, Reverse((task.urgency(now) * 100.0) as i64), task.id));
+    }
+    sections
+}
+
+/// Print the open tasks in sections: overdue, today, tomorrow, the rest of
+/// this week, later and without a due date.
+pub fn print_agenda(storage: &mut dyn Storage, format: OutputFormat, columns: &Columns) -> Result<()> {
+    let journal = storage.load()?;
+    let today = Local::now().date_naive();
+    let sections = sections(&journal, today);
+
+    if format.is_machine() {
+        // Every section is there, even when empty, so scripts can rely on it.
+        let open = journal.open_ids();
+        let groups: Vec<(&str, Vec<Entry>)> = sections
+            .iter()
+            .map(|(section, tasks)| {
+                let entries = tasks
+                    .iter()
+                    .map(|&task| Entry {
+                        task,
+                        open: &open,
+                        source: None,
+                    })
+                    .collect();
+                (section.key(), entries)
+            })
+            .collect();
+        output::print_groups(&groups, format, columns);
+        return Ok(());
+    }
+    print!("{}", render_agenda(&sections, today, Terminal::stdout()));
+    Ok(())
+}
+
+fn render_agenda(sections: &[(Section, Vec<&Task>)], today: NaiveDate, terminal: Terminal) -> String {
+    if sections.iter().all(|(_, tasks)| tasks.is_empty()) {
+        return "No open tasks.\n".to_string();
+    }
+    let date_format = &config::appearance().date_format;
+    let mut table = Table::new(["When", "ID", "Task", "Due", "Priority", "Project", "Tags"]).flexible(2);
+    for (section, tasks) in sections {
+        for (index, task) in tasks.iter().enumerate() {
+            let when = if index == 0 { section.label() } else { "" };
+            let due = task
+                .due
+                .map(|due| format!("{} {}", due.format(date_format), due.format("%a")))
+                .unwrap_or_default();
+            let tags: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+            table.push(
+                vec![
+                    Cell::coloured(when, Some(Colour::Bold)),
+                    task.id.to_string().into(),
+                    task.text.as_str().into(),
+                    Cell::coloured(due, output::due_colour(task, today)),
+                    Cell::coloured(
+                        task.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+                        task.priority.and_then(output::priority_colour),
+                    ),
+                    task.project.clone().unwrap_or_default().into(),
+                    tags.join(" ").into(),
+                ],
+                None,
+            );
+        }
+    }
+    table.render(terminal)
+}
+
+/// Read `--month`: `2026-11`, or a date in the month such as `next month`.
+pub fn parse_month(s: &str) -> std::result::Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d")
+        .ok()
+        .or_else(|| dates::parse(s, Local::now().date_naive()))
+        .and_then(|date| date.with_day(1))
+        .ok_or_else(|| format!("Unknown month '{}', expected e.g. 2026-11 or 'next month'", s))
+}
+
+/// Print a month, given by its first day, as a grid with the number of open
+/// tasks due each day. Defaults to the current month.
+pub fn print_calendar(storage: &mut dyn Storage, month: Option<NaiveDate>, format: OutputFormat) -> Result<()> {
+    let journal = storage.load()?;
+    let today = Local::now().date_naive();
+    let month = month.unwrap_or_else(|| today.with_day(1).expect("every month has a first day"));
+    let next = month + Months::new(1);
+    let mut due: BTreeMap<NaiveDate, usize> = month.iter_days().take_while(|day| *day < next).map(|day| (day, 0)).collect();
+    for task in journal.tasks.iter().filter(|task| !task.is_completed()) {
+        if let Some(count) = task.due.and_then(|day| due.get_mut(&day)) {
+            *count += 1;
+        }
+    }
+
+    match format {
+        OutputFormat::Json => {
+            let days: Vec<_> = due.iter().map(|(day, count)| json!({ "date": day, "due": count })).collect();
+            let value = json!({ "month": month.format("%Y-%m").to_string(), "days": days });
+            println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default());
+        }
+        OutputFormat::Jsonl => {
+            for (day, count) in &due {
+                println!("{}", json!({ "date": day, "due": count }));
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            print!("{}", output::delimited_line(format, &["date".to_string(), "due".to_string()]));
+            for (day, count) in &due {
+                print!("{}", output::delimited_line(format, &[day.to_string(), count.to_string()]));
+            }
+        }
+        OutputFormat::Text | OutputFormat::Table => {
+            print!("{}", render_calendar(month, &due, today, Terminal::stdout()))
+        }
+    }
+    Ok(())
+}
+
+/// How wide a day is in the grid: the day of the month and a count of up to
+/// `(99+)`.
+const DAY_WIDTH: usize = 8;
+
+fn render_calendar(month: NaiveDate, due: &BTreeMap<NaiveDate, usize>, today: NaiveDate, terminal: Terminal) -> String {
+    let appearance = config::appearance();
+    let width = DAY_WIDTH * 7 + 6;
+    let title = month.format("%B %Y").to_string();
+    let mut out = format!("{:^width$}", title, width = width).trim_end().to_string() + "\n";
+    let weekdays: Vec<String> = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+        .iter()
+        .map(|day| format!("{:<width$}", day, width = DAY_WIDTH))
+        .collect();
+    out += weekdays.join(" ").trim_end();
+    out.push('\n');
+
+    let mut line = " ".repeat((DAY_WIDTH + 1) * month.weekday().num_days_from_monday() as usize);
+    for (&day, &count) in due {
+        let count_text = match count {
+            0 => String::new(),
+            1..=99 => format!("({})", count),
+            _ => "(99+)".to_string(),
+        };
+        let text = format!("{:>2} {}", day.day(), count_text);
+        let colour = if day == today {
+            Some(Colour::Highlight)
+        } else if count > 0 && day < today {
+            appearance.overdue
+        } else if count > 0 {
+            Some(Colour::Bold)
+        } else {
+            None
+        };
+        match colour.filter(|_| terminal.colour) {
+            Some(colour) => line += &table::paint(&text, colour),
+            None => line += &text,
+        }
+        line += &" ".repeat(DAY_WIDTH.saturating_sub(text.len()));
+        if day.weekday() == chrono::Weekday::Sun {
+            writeln!(out, "{}", line.trim_end()).unwrap();
+            line.clear();
+        } else {
+            line.push(' ');
+        }
+    }
+    if !line.trim().is_empty() {
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+
+    let total: usize = due.values().sum();
+    let overdue: usize = due.range(..today).map(|(_, count)| count).sum();
+    out.push('\n');
+    match (total, overdue) {
+        (0, _) => out += "No open tasks are due this month.\n",
+        (_, 0) => writeln!(out, "{} open task(s) due this month.", total).unwrap(),
+        _ => writeln!(out, "{} open task(s) due this month, {} of them overdue.", total, overdue).unwrap(),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn tasks_fall_into_sections() {
+        // A Thursday.
+        let today = date("2026-10-22");
+        let mut journal = Journal::default();
+        for due in [None, Some("2026-10-30"), Some("2026-10-25"), Some("2026-10-23"), Some("2026-10-22"), Some("2026-10-01")] {
+            let mut task = Task::new(format!("due {:?}", due));
+            task.due = due.map(date);
+            journal.push(task);
+        }
+        let mut done = Task::new("done".to_string());
+        done.complete();
+        journal.push(done);
+
+        let sections: Vec<(Section, Vec<u64>)> = sections(&journal, today)
+            .into_iter()
+            .map(|(section, tasks)| (section, tasks.iter().map(|task| task.id).collect()))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                (Section::Overdue, vec![6]),
+                (Section::Today, vec![5]),
+                (Section::Tomorrow, vec![4]),
+                (Section::ThisWeek, vec![3]),
+                (Section::Later, vec![2]),
+                (Section::NoDate, vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn months_are_drawn_as_weeks() {
+        let month = date("2026-11-01");
+        let mut due: BTreeMap<NaiveDate, usize> =
+            month.iter_days().take_while(|day| day.month() == 11).map(|day| (day, 0)).collect();
+        due.insert(date("2026-11-03"), 2);
+        due.insert(date("2026-11-20"), 12);
+        due.insert(date("2026-11-26"), 100);
+        let calendar = render_calendar(month, &due, date("2026-11-05"), Terminal::plain());
+        let lines: Vec<&str> = calendar.lines().collect();
+        assert_eq!(lines[0], format!("{}November 2026", " ".repeat(24)));
+        assert_eq!(lines[1], "Mon      Tue      Wed      Thu      Fri      Sat      Sun");
+        // November 2026 starts on a Sunday.
+        assert_eq!(lines[2], format!("{} 1", " ".repeat(54)));
+        assert_eq!(lines[3], " 2        3 (2)    4        5        6        7        8");
+        assert_eq!(lines[5], "16       17       18       19       20 (12)  21       22");
+        // Big counts are capped so the days after them stay in their columns.
+        assert_eq!(lines[6], "23       24       25       26 (99+) 27       28       29");
+        assert_eq!(lines.last(), Some(&"114 open task(s) due this month, 2 of them overdue."));
+    }
+}
@@ -1,20 +1,468 @@
+use crate::agenda::parse_month;
+use crate::config::parse_lock_timeout;
+use crate::dates::parse_due;
+use crate::interchange::Format;
+use crate::output::{Columns, OutputFormat};
+use crate::recurrence::Recurrence;
+use crate::report::Period;
+use crate::timesheet::{parse_duration, parse_time, Rounding};
+use crate::storage::Backend;
+use crate::tasks::{parse_project, parse_tag, Attribute, Priority, SortKey};
+use chrono::{Duration, NaiveDate, NaiveTime};
 use std::path::PathBuf;
+use structopt::clap::ArgGroup;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 pub enum Action {
     /// Write tasks to the journal file.
     Add {
-        /// The task description text.
+        /// The task description text. Fields can be written inline, as in
+        /// 'deploy api due:fri p:H +release project:infra every:week'.
         #[structopt()]
         task: String,
+
+        /// When the task is due, as YYYY-MM-DD or e.g. tomorrow, fri, in 3 days or eom.
+        #[structopt(short, long, parse(try_from_str = parse_due))]
+        due: Option<NaiveDate>,
+
+        /// How important the task is: high, medium or low.
+        #[structopt(short, long)]
+        priority: Option<Priority>,
+
+        /// Tag the task, e.g. `--tag +oncall`. Can be repeated.
+        #[structopt(short, long = "tag", parse(try_from_str = parse_tag))]
+        tags: Vec<String>,
+
+        /// The project the task belongs to.
+        #[structopt(long, parse(try_from_str = parse_project))]
+        project: Option<String>,
+
+        /// Repeat the task: daily, weekly, weekly:mon,thu, monthly:5, every:3d or an
+        /// RRULE such as FREQ=WEEKLY;INTERVAL=2;BYDAY=FR. Completing it adds the next one.
+        #[structopt(short, long)]
+        recur: Option<Recurrence>,
+
+        /// Make the task a subtask of another one.
+        #[structopt(long)]
+        parent: Option<u64>,
+
+        /// Show how the task was read without adding it.
+        #[structopt(long)]
+        dry_run: bool,
     },
-    /// Remove an entry from the journal file by position.
+    /// Mark a task as completed. It is kept as history until purged.
     Done {
+        /// The ID shown by `list`.
         #[structopt()]
-        position: usize,
+        id: u64,
+
+        /// Complete the task's open subtasks too. Without it, a task with open
+        /// subtasks can't be completed.
+        #[structopt(short, long)]
+        recursive: bool,
+    },
+    /// List open tasks in the journal file, with their IDs.
+    List {
+        /// A filter such as 'project:infra and (due < 2026-11-01 or +urgent) and not +blocked'.
+        #[structopt()]
+        query: Option<String>,
+
+        /// Show completed tasks too.
+        #[structopt(short, long)]
+        all: bool,
+
+        /// Show only completed tasks.
+        #[structopt(short, long, conflicts_with = "all")]
+        completed: bool,
+
+        /// Sort by urgency, due, created or priority. Defaults to the `sort` setting.
+        #[structopt(short, long)]
+        sort: Option<SortKey>,
+
+        /// Only show tasks with this tag. Can be repeated.
+        #[structopt(short, long = "tag", parse(try_from_str = parse_tag))]
+        tags: Vec<String>,
+
+        /// Only show tasks in this project.
+        #[structopt(long, parse(try_from_str = parse_project))]
+        project: Option<String>,
+
+        /// Only show tasks that aren't waiting on any open task.
+        #[structopt(long)]
+        ready: bool,
+
+        /// Only show tasks that are waiting on an open task.
+        #[structopt(long, conflicts_with = "ready")]
+        blocked: bool,
+
+        /// Print as text, table, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+
+        /// The fields to print, such as 'id,text,due'. Implies a table in text format.
+        #[structopt(long)]
+        fields: Option<Columns>,
+
+        /// Use the global journal even inside a project with its own.
+        #[structopt(long)]
+        global: bool,
+
+        /// List the global journal and every known project journal, each task
+        /// labelled with the journal it's in.
+        #[structopt(long, conflicts_with = "global")]
+        everywhere: bool,
+    },
+    /// Create a project journal, used instead of the global one in and below its directory.
+    Init {
+        /// Where to create it. Defaults to the current directory.
+        #[structopt(parse(from_os_str))]
+        dir: Option<PathBuf>,
+    },
+    /// Remove tasks from the journal file for good.
+    Purge {
+        /// The tasks to remove. Without IDs, every completed task is removed.
+        #[structopt()]
+        ids: Vec<u64>,
+    },
+    /// Show every tag in use with its task counts.
+    Tags,
+    /// Add or remove tags on a task.
+    Tag(TagAction),
+    /// Change the text or other fields of a task.
+    Modify {
+        /// The ID shown by `list`.
+        #[structopt()]
+        id: u64,
+
+        /// New text for the task.
+        #[structopt(long)]
+        text: Option<String>,
+
+        /// When the task is due, as YYYY-MM-DD or e.g. tomorrow, fri, in 3 days or eom.
+        #[structopt(short, long, parse(try_from_str = parse_due))]
+        due: Option<NaiveDate>,
+
+        /// How important the task is: high, medium or low.
+        #[structopt(short, long)]
+        priority: Option<Priority>,
+
+        /// Add a tag. Can be repeated.
+        #[structopt(short, long = "tag", parse(try_from_str = parse_tag))]
+        tags: Vec<String>,
+
+        /// Remove a tag. Can be repeated.
+        #[structopt(long = "untag", parse(try_from_str = parse_tag))]
+        untag: Vec<String>,
+
+        /// The project the task belongs to.
+        #[structopt(long, parse(try_from_str = parse_project))]
+        project: Option<String>,
+
+        /// How the task repeats, as for `add --recur`.
+        #[structopt(short, long)]
+        recur: Option<Recurrence>,
+
+        /// Make the task a subtask of another one.
+        #[structopt(long)]
+        parent: Option<u64>,
+
+        /// Unset a field: due, priority, project, tags, recur, parent or depends. Can be repeated.
+        #[structopt(long)]
+        clear: Vec<Attribute>,
+    },
+    /// Edit a task, or the whole journal, as JSON in $VISUAL or $EDITOR.
+    Edit {
+        /// The task to edit. Without it, the whole journal is opened, and tasks
+        /// can also be reordered or removed.
+        #[structopt()]
+        id: Option<u64>,
+    },
+    /// Move a task to another place in the journal's order.
+    #[structopt(group = ArgGroup::with_name("place"))]
+    Move {
+        /// The task to move.
+        #[structopt()]
+        id: u64,
+
+        /// Put it just before this task.
+        #[structopt(long, group = "place")]
+        before: Option<u64>,
+
+        /// Put it just after this task.
+        #[structopt(long, group = "place")]
+        after: Option<u64>,
+
+        /// Put it first.
+        #[structopt(long, group = "place")]
+        top: bool,
+
+        /// Put it last.
+        #[structopt(long, group = "place")]
+        bottom: bool,
+    },
+    /// Make a task wait for other tasks to be done first.
+    Depends {
+        /// The task that has to wait.
+        #[structopt()]
+        id: u64,
+
+        /// The tasks it waits for.
+        #[structopt(required = true)]
+        depends: Vec<u64>,
+
+        /// Stop waiting for these tasks instead.
+        #[structopt(short, long)]
+        remove: bool,
+    },
+    /// Find tasks by words in their text, project or tags, completed ones included.
+    Search {
+        /// What to look for. Case, accents and small typos don't matter.
+        #[structopt(required = true)]
+        terms: Vec<String>,
+
+        /// Show at most this many of the best matches.
+        #[structopt(short = "n", long, default_value = "20")]
+        limit: usize,
+
+        /// Print as text, table, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+
+        /// The fields to print, such as 'id,text,due'. Implies a table in text format.
+        #[structopt(long)]
+        fields: Option<Columns>,
+    },
+    /// Read and change settings, see `config list`.
+    Config(ConfigAction),
+    /// Browse, filter, add, complete and edit tasks in a full-screen interface.
+    Tui,
+    /// Show open tasks by when they're due: overdue, today, tomorrow, this week,
+    /// later and without a date.
+    Agenda {
+        /// Print as text, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+
+        /// The fields to print in the machine formats, such as 'id,text,due'.
+        #[structopt(long)]
+        fields: Option<Columns>,
     },
-    /// List all tasks in the journal file.
+    /// Show a month with the number of open tasks due each day.
+    Calendar {
+        /// The month to show, e.g. 2026-11 or 'next month'. Defaults to this one.
+        #[structopt(short, long, parse(try_from_str = parse_month))]
+        month: Option<NaiveDate>,
+
+        /// Print as text, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Start the timer on a task, stopping the one that's running.
+    Start {
+        /// The ID shown by `list`.
+        #[structopt()]
+        id: u64,
+    },
+    /// Stop the running timer.
+    Stop,
+    /// Record time spent on a task after the fact.
+    Log {
+        /// The ID shown by `list`.
+        #[structopt()]
+        id: u64,
+
+        /// How long, e.g. 1h30m, 90m, 1.5h or 1:30.
+        #[structopt(parse(try_from_str = parse_duration))]
+        spent: Duration,
+
+        /// The day, e.g. 2026-10-12, yesterday or mon. Defaults to today.
+        #[structopt(short, long, parse(try_from_str = parse_due))]
+        date: Option<NaiveDate>,
+
+        /// When the time started, as HH:MM. Without it, the entry ends now
+        /// for today and starts at 09:00 on other days.
+        #[structopt(long, parse(try_from_str = parse_time))]
+        at: Option<NaiveTime>,
+    },
+    /// Add up the time spent per task, tag and day.
+    Timesheet {
+        /// Cover the whole week, Monday to Sunday, rather than one day.
+        #[structopt(short, long)]
+        week: bool,
+
+        /// The day, or a day in the week, to cover. Defaults to today.
+        #[structopt(short, long, parse(try_from_str = parse_due))]
+        date: Option<NaiveDate>,
+
+        /// Round each task's time per day to a multiple of this, e.g. 15m or 6m.
+        #[structopt(short, long, parse(try_from_str = parse_duration))]
+        round: Option<Duration>,
+
+        /// Which way to round: nearest, up or down.
+        #[structopt(long, default_value = "nearest")]
+        rounding: Rounding,
+
+        /// Print as text, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Show tasks created and completed over time, how long tasks take and how
+    /// old the open ones are, overall and per tag and project.
+    Report {
+        /// Group counts by day, week or month.
+        #[structopt(short, long, default_value = "week")]
+        by: Period,
+
+        /// How many periods to cover, up to and including the current one.
+        #[structopt(short = "n", long, default_value = "12")]
+        periods: u32,
+
+        /// Print as text, json, jsonl, csv or tsv.
+        #[structopt(short, long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Print the dependency graph in Graphviz DOT, e.g. `rusty-journal graph | dot -Tsvg`.
+    Graph {
+        /// Include completed tasks.
+        #[structopt(short, long)]
+        all: bool,
+    },
+    /// Add the tasks in a file written by another app.
+    Import {
+        /// The file's format: todotxt, or ics to merge in to-dos from a calendar by UID.
+        #[structopt(short, long)]
+        format: Format,
+
+        /// The file to read.
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Write every task, completed ones included, in a format other apps read.
+    Export {
+        /// The format to write: todotxt or ics.
+        #[structopt(short, long)]
+        format: Format,
+
+        /// The file to write. Defaults to standard output.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Copy the journal into a new one that uses another storage backend.
+    Migrate {
+        /// The backend to copy to: json or sqlite.
+        #[structopt(long)]
+        to: Backend,
+
+        /// Where to put the copy. Defaults to the journal path with a .db or .json extension.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Undo the last change to the journal.
+    Undo {
+        /// How many changes to undo.
+        #[structopt(default_value = "1")]
+        count: usize,
+
+        /// Show recent changes instead of undoing anything.
+        #[structopt(short, long)]
+        list: bool,
+    },
+    /// Redo a change that was undone.
+    Redo {
+        /// How many changes to redo.
+        #[structopt(default_value = "1")]
+        count: usize,
+    },
+}
+
+impl Action {
+    /// The subcommand's name, used to label entries in the undo history.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Add { .. } => "add",
+            Action::Done { .. } => "done",
+            Action::List { .. } => "list",
+            Action::Purge { .. } => "purge",
+            Action::Tags => "tags",
+            Action::Tag(TagAction::Add { .. }) => "tag add",
+            Action::Tag(TagAction::Remove { .. }) => "tag remove",
+            Action::Modify { .. } => "modify",
+            Action::Edit { .. } => "edit",
+            Action::Move { .. } => "move",
+            Action::Depends { .. } => "depends",
+            Action::Search { .. } => "search",
+            Action::Config(ConfigAction::Get { .. }) => "config get",
+            Action::Config(ConfigAction::Set { .. }) => "config set",
+            Action::Config(ConfigAction::List) => "config list",
+            Action::Tui => "tui",
+            Action::Report { .. } => "report",
+            Action::Agenda { .. } => "agenda",
+            Action::Calendar { .. } => "calendar",
+            Action::Start { .. } => "start",
+            Action::Stop => "stop",
+            Action::Log { .. } => "log",
+            Action::Timesheet { .. } => "timesheet",
+            Action::Init { .. } => "init",
+            Action::Graph { .. } => "graph",
+            Action::Import { .. } => "import",
+            Action::Export { .. } => "export",
+            Action::Migrate { .. } => "migrate",
+            Action::Undo { .. } => "undo",
+            Action::Redo { .. } => "redo",
+        }
+    }
+
+    /// The format the command prints in, which errors follow too.
+    pub fn output_format(&self) -> OutputFormat {
+        match self {
+            Action::List { format, .. }
+            | Action::Search { format, .. }
+            | Action::Report { format, .. }
+            | Action::Timesheet { format, .. }
+            | Action::Agenda { format, .. }
+            | Action::Calendar { format, .. } => *format,
+            _ => OutputFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum TagAction {
+    /// Add tags to a task.
+    Add {
+        #[structopt()]
+        id: u64,
+
+        #[structopt(required = true, parse(try_from_str = parse_tag))]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a task.
+    Remove {
+        #[structopt()]
+        id: u64,
+
+        #[structopt(required = true, parse(try_from_str = parse_tag))]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigAction {
+    /// Print the value of a setting, such as `date_format` or `aliases.today`.
+    Get {
+        #[structopt()]
+        key: String,
+    },
+    /// Change a setting in the config file.
+    Set {
+        #[structopt()]
+        key: String,
+
+        #[structopt()]
+        value: String,
+    },
+    /// Show every setting with its value and where it comes from.
     List,
 }
 
@@ -27,7 +475,20 @@ pub struct CommandLineArgs {
     #[structopt(subcommand)]
     pub action: Action,
 
-    /// Use a different journal file.
+    /// Use a different journal file than the nearest project journal or the `journal` setting.
     #[structopt(parse(from_os_str), short, long)]
     pub journal_file: Option<PathBuf>,
+
+    /// How the journal is stored: json or sqlite. Defaults to sqlite for
+    /// .db/.sqlite/.sqlite3 files and json otherwise.
+    #[structopt(short, long)]
+    pub backend: Option<Backend>,
+
+    /// How many changes to remember for `undo`, 100 by default. 0 turns the history off.
+    #[structopt(long)]
+    pub history_depth: Option<usize>,
+
+    /// Seconds to wait for another rusty-journal process to release the journal, 10 by default.
+    #[structopt(long, parse(try_from_str = parse_lock_timeout))]
+    pub lock_timeout: Option<f64>,
 }
\ No newline at end of file
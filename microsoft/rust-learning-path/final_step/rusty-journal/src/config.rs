@@ -0,0 +1,581 @@
+//! Settings from `$XDG_CONFIG_HOME/rusty-journal/config.toml`, falling back
+//! to `~/.config/rusty-journal/config.toml`, or the file `$RUSTY_JOURNAL_CONFIG`
+//! names:
+//!
+//! ```toml
+//! journal = "~/Documents/journal.json"
+//! date_format = "%d/%m/%Y"
+//! sort = "urgency"
+//!
+//! [colours]
+//! high = "magenta"
+//! overdue = "red"
+//!
+//! [aliases]
+//! today = "list 'due <= today'"
+//! ```
+//!
+//! Every setting can be overridden with an environment variable named after
+//! it, such as `RUSTY_JOURNAL_DATE_FORMAT` or `RUSTY_JOURNAL_COLOURS_HIGH`.
+//! Command-line flags win over both, so the order is flag, environment, file
+//! and then the built-in default.
+
+use crate::storage::{self, Backend};
+use crate::table::Colour;
+use crate::tasks::SortKey;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Write};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use toml_edit::{value, DocumentMut, Item};
+
+const ENV_PREFIX: &str = "RUSTY_JOURNAL_";
+
+/// A setting the config file knows about.
+struct Setting {
+    key: &'static str,
+    default: Option<&'static str>,
+    numeric: bool,
+    check: fn(&str) -> std::result::Result<(), String>,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "journal",
+        default: None,
+        numeric: false,
+        check: |_| Ok(()),
+    },
+    Setting {
+        key: "backend",
+        default: None,
+        numeric: false,
+        check: |s| s.parse::<Backend>().map(drop),
+    },
+    Setting {
+        key: "history_depth",
+        default: Some("100"),
+        numeric: true,
+        check: |s| s.parse::<usize>().map(drop).map_err(|_| format!("'{}' isn't a whole number", s)),
+    },
+    Setting {
+        key: "lock_timeout",
+        default: Some("10"),
+        numeric: true,
+        check: |s| parse_lock_timeout(s).map(drop),
+    },
+    Setting {
+        key: "date_format",
+        default: Some("%Y-%m-%d"),
+        numeric: false,
+        check: check_date_format,
+    },
+    Setting {
+        key: "sort",
+        default: None,
+        numeric: false,
+        check: |s| s.parse::<SortKey>().map(drop),
+    },
+    Setting {
+        key: "colour",
+        default: Some("auto"),
+        numeric: false,
+        check: |s| s.parse::<ColourChoice>().map(drop),
+    },
+    Setting {
+        key: "colours.high",
+        default: Some("red"),
+        numeric: false,
+        check: |s| parse_colour(s).map(drop),
+    },
+    Setting {
+        key: "colours.medium",
+        default: Some("yellow"),
+        numeric: false,
+        check: |s| parse_colour(s).map(drop),
+    },
+    Setting {
+        key: "colours.low",
+        default: Some("cyan"),
+        numeric: false,
+        check: |s| parse_colour(s).map(drop),
+    },
+    Setting {
+        key: "colours.overdue",
+        default: Some("red"),
+        numeric: false,
+        check: |s| parse_colour(s).map(drop),
+    },
+    Setting {
+        key: "colours.today",
+        default: Some("yellow"),
+        numeric: false,
+        check: |s| parse_colour(s).map(drop),
+    },
+];
+
+/// Where a setting's value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Environment,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File => write!(f, "config file"),
+            Source::Environment => write!(f, "environment"),
+        }
+    }
+}
+
+/// Whether to use colour: `auto` only does on a terminal without `$NO_COLOR`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColourChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColourChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColourChoice::Auto),
+            "always" => Ok(ColourChoice::Always),
+            "never" => Ok(ColourChoice::Never),
+            _ => Err(format!("Unknown colour choice '{}', expected auto, always or never", s)),
+        }
+    }
+}
+
+/// How output looks, from the settings of the same names.
+#[derive(Debug, Clone)]
+pub struct Appearance {
+    pub date_format: String,
+    pub colour: ColourChoice,
+    pub high: Option<Colour>,
+    pub medium: Option<Colour>,
+    pub low: Option<Colour>,
+    pub overdue: Option<Colour>,
+    pub today: Option<Colour>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance {
+            date_format: "%Y-%m-%d".to_string(),
+            colour: ColourChoice::Auto,
+            high: Some(Colour::Red),
+            medium: Some(Colour::Yellow),
+            low: Some(Colour::Cyan),
+            overdue: Some(Colour::Red),
+            today: Some(Colour::Yellow),
+        }
+    }
+}
+
+static APPEARANCE: OnceLock<Appearance> = OnceLock::new();
+
+/// The appearance `main` set up, or the default one.
+pub fn appearance() -> &'static Appearance {
+    APPEARANCE.get_or_init(Appearance::default)
+}
+
+pub fn set_appearance(appearance: Appearance) {
+    let _ = APPEARANCE.set(appearance);
+}
+
+/// The config file and the environment, read once at startup.
+#[derive(Debug)]
+pub struct Config {
+    path: Option<PathBuf>,
+    document: DocumentMut,
+    /// `RUSTY_JOURNAL_*` variables, without the prefix and lowercased.
+    env: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        let path = env::var_os("RUSTY_JOURNAL_CONFIG").map(PathBuf::from).or_else(default_path);
+        let text = match &path {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+            },
+            None => String::new(),
+        };
+        let env = env::vars().filter_map(|(name, value)| {
+            let name = name.strip_prefix(ENV_PREFIX)?;
+            (name != "CONFIG").then(|| (name.to_lowercase(), value))
+        });
+        Config::from_parts(path, &text, env)
+    }
+
+    fn from_parts(path: Option<PathBuf>, text: &str, env: impl Iterator<Item = (String, String)>) -> Result<Config> {
+        let location = path.as_deref().map_or_else(|| "config".to_string(), |path| path.display().to_string());
+        let document: DocumentMut = text
+            .parse()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", location, e)))?;
+        let config = Config {
+            path,
+            document,
+            env: env.collect(),
+        };
+
+        // Catch mistakes up front rather than when a setting is first used.
+        for (key, item) in config.document.iter() {
+            let keys: Vec<String> = match item.as_table_like() {
+                Some(table) => table.iter().map(|(name, _)| format!("{}.{}", key, name)).collect(),
+                None => vec![key.to_string()],
+            };
+            for key in keys {
+                let value = config.file_value(&key).unwrap_or_default();
+                check(&key, &value).map_err(|e| invalid(format!("{}: {}", location, e)))?;
+            }
+        }
+        for setting in SETTINGS {
+            if let Some(value) = config.env.get(&env_name(setting.key)) {
+                (setting.check)(value)
+                    .map_err(|e| invalid(format!("{}{}: {}", ENV_PREFIX, env_name(setting.key).to_uppercase(), e)))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// The value of `key` and where it came from, or `None` if it isn't set
+    /// and has no default.
+    pub fn get(&self, key: &str) -> Option<(String, Source)> {
+        if let Some(value) = self.env.get(&env_name(key)) {
+            return Some((value.clone(), Source::Environment));
+        }
+        if let Some(value) = self.file_value(key) {
+            return Some((value, Source::File));
+        }
+        let default = SETTINGS.iter().find(|setting| setting.key == key)?.default?;
+        Some((default.to_string(), Source::Default))
+    }
+
+    /// The value of `key` parsed; the values were checked when loading.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|(value, _)| value.parse().ok())
+    }
+
+    /// The journal file, with a leading `~` meaning the home directory.
+    pub fn journal(&self) -> Option<PathBuf> {
+        let (path, _) = self.get("journal")?;
+        match path.strip_prefix("~/") {
+            Some(rest) => home::home_dir().map(|home| home.join(rest)),
+            None => Some(PathBuf::from(path)),
+        }
+    }
+
+    pub fn appearance(&self) -> Appearance {
+        let colour = |key: &str| self.get(key).and_then(|(value, _)| parse_colour(&value).ok().flatten());
+        Appearance {
+            date_format: self.get("date_format").map(|(value, _)| value).unwrap_or_default(),
+            colour: self.parse("colour").unwrap_or_default(),
+            high: colour("colours.high"),
+            medium: colour("colours.medium"),
+            low: colour("colours.low"),
+            overdue: colour("colours.overdue"),
+            today: colour("colours.today"),
+        }
+    }
+
+    /// Every alias, from the file and the environment.
+    pub fn aliases(&self) -> BTreeMap<String, (String, Source)> {
+        let mut aliases = BTreeMap::new();
+        if let Some(table) = self.document.get("aliases").and_then(Item::as_table_like) {
+            for (name, _) in table.iter() {
+                let key = format!("aliases.{}", name);
+                aliases.insert(name.to_string(), (self.file_value(&key).unwrap_or_default(), Source::File));
+            }
+        }
+        for (name, value) in &self.env {
+            if let Some(name) = name.strip_prefix("aliases_") {
+                aliases.insert(name.to_string(), (value.clone(), Source::Environment));
+            }
+        }
+        aliases
+    }
+
+    /// `args` with the alias in place of the subcommand expanded, if it is one.
+    pub fn expand_alias(&self, args: &[OsString]) -> Option<Vec<OsString>> {
+        // Skip the program name and global options to find the subcommand.
+        let mut index = 1;
+        while let Some(arg) = args.get(index).and_then(|arg| arg.to_str()) {
+            if !arg.starts_with('-') {
+                break;
+            }
+            let takes_value = matches!(
+                arg,
+                "-j" | "--journal-file" | "-b" | "--backend" | "--history-depth" | "--lock-timeout"
+            );
+            index += if takes_value { 2 } else { 1 };
+        }
+        let name = args.get(index)?.to_str()?;
+        let (expansion, _) = self.aliases().remove(name)?;
+
+        let mut expanded = args[..index].to_vec();
+        expanded.extend(split_words(&expansion).into_iter().map(OsString::from));
+        expanded.extend_from_slice(&args[index + 1..]);
+        Some(expanded)
+    }
+
+    /// A value from the file, as text whatever its TOML type.
+    fn file_value(&self, key: &str) -> Option<String> {
+        let item = match key.split_once('.') {
+            Some((table, name)) => self.document.get(table)?.get(name)?,
+            None => self.document.get(key)?,
+        };
+        let value = item.as_value()?;
+        if let Some(text) = value.as_str() {
+            return Some(text.to_string());
+        }
+        Some(value.clone().decorated("", "").to_string())
+    }
+}
+
+/// Print the value of one setting.
+pub fn get(config: &Config, key: &str) -> Result<()> {
+    check_key(key)?;
+    let value = match key.strip_prefix("aliases.") {
+        Some(name) => config.aliases().remove(name).map(|(value, _)| value),
+        None => config.get(key).map(|(value, _)| value),
+    };
+    match value {
+        Some(value) => {
+            println!("{}", value);
+            Ok(())
+        }
+        None => Err(Error::new(ErrorKind::NotFound, format!("'{}' isn't set", key))),
+    }
+}
+
+/// Change one setting in the config file, keeping the rest of it as it is.
+pub fn set(config: &Config, key: &str, new_value: &str) -> Result<()> {
+    check(key, new_value).map_err(invalid)?;
+    let path = config
+        .path
+        .as_ref()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "Can't tell where the config file goes; set $XDG_CONFIG_HOME"))?;
+
+    let mut document = config.document.clone();
+    let numeric = SETTINGS.iter().any(|setting| setting.key == key && setting.numeric);
+    let item = match (numeric, new_value.parse::<i64>(), new_value.parse::<f64>()) {
+        (true, Ok(number), _) => value(number),
+        (true, _, Ok(number)) => value(number),
+        _ => value(new_value),
+    };
+    match key.split_once('.') {
+        Some((table, name)) => {
+            if !document.contains_key(table) {
+                document[table] = toml_edit::table();
+            }
+            document[table][name] = item
+        }
+        None => document[key] = item,
+    }
+    write_file(path, &document.to_string())?;
+
+    if config.env.contains_key(&env_name(key)) {
+        println!(
+            "Set {} in {}, but {}{} overrides it.",
+            key,
+            path.display(),
+            ENV_PREFIX,
+            env_name(key).to_uppercase()
+        );
+    } else {
+        println!("Set {} in {}.", key, path.display());
+    }
+    Ok(())
+}
+
+/// Print every setting with its value and where the value came from.
+pub fn list(config: &Config) -> Result<()> {
+    if let Some(path) = &config.path {
+        println!("# {}", path.display());
+    }
+    for setting in SETTINGS {
+        match config.get(setting.key) {
+            Some((value, source)) => println!("{} = {}  ({})", setting.key, value, source),
+            None => println!("{} =  (not set)", setting.key),
+        }
+    }
+    for (name, (value, source)) in config.aliases() {
+        println!("aliases.{} = {}  ({})", name, value, source);
+    }
+    Ok(())
+}
+
+fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home::home_dir().map(|home| home.join(".config")))?;
+    Some(base.join("rusty-journal").join("config.toml"))
+}
+
+fn write_file(path: &Path, text: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Replace what a symlink points to rather than the link, as config files
+    // are often linked from a dotfiles repository.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    storage::write_atomically(&path, |file| file.write_all(text.as_bytes()))
+}
+
+/// `colours.high` to `colours_high`, the environment variable without its prefix.
+fn env_name(key: &str) -> String {
+    key.replace('.', "_")
+}
+
+fn check_key(key: &str) -> Result<()> {
+    let known = SETTINGS.iter().any(|setting| setting.key == key)
+        || key.strip_prefix("aliases.").is_some_and(|name| !name.is_empty());
+    if known {
+        return Ok(());
+    }
+    let keys: Vec<&str> = SETTINGS.iter().map(|setting| setting.key).collect();
+    Err(invalid(format!(
+        "Unknown setting '{}', expected one of {} or aliases.<name>",
+        key,
+        keys.join(", ")
+    )))
+}
+
+fn check(key: &str, value: &str) -> std::result::Result<(), String> {
+    check_key(key).map_err(|e| e.to_string())?;
+    if key.starts_with("aliases.") {
+        return match split_words(value).is_empty() {
+            true => Err(format!("The alias {} is empty", key)),
+            false => Ok(()),
+        };
+    }
+    let setting = SETTINGS.iter().find(|setting| setting.key == key).expect("checked above");
+    (setting.check)(value).map_err(|e| format!("{}: {}", key, e))
+}
+
+/// Read a lock timeout, a number of seconds that's at least 0, for the
+/// setting and `--lock-timeout`.
+pub fn parse_lock_timeout(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(seconds) if Duration::try_from_secs_f64(seconds).is_ok() => Ok(seconds),
+        _ => Err(format!("'{}' isn't a number of seconds", s)),
+    }
+}
+
+fn check_date_format(format: &str) -> std::result::Result<(), String> {
+    let mut text = String::new();
+    let date = NaiveDate::from_ymd_opt(2026, 1, 31).expect("valid date");
+    match write!(text, "{}", date.format(format)) {
+        Ok(()) if !text.is_empty() => Ok(()),
+        _ => Err(format!("'{}' isn't a date format such as %Y-%m-%d", format)),
+    }
+}
+
+/// A colour name, or `none` for no colour.
+fn parse_colour(s: &str) -> std::result::Result<Option<Colour>, String> {
+    match s.to_lowercase().as_str() {
+        "none" => Ok(None),
+        _ => s.parse().map(Some),
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Split an alias into arguments at spaces, keeping quoted parts together.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, env: &[(&str, &str)]) -> Result<Config> {
+        let env = env.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        Config::from_parts(None, text, env.collect::<Vec<_>>().into_iter())
+    }
+
+    #[test]
+    fn environment_beats_file_beats_default() {
+        let file = "date_format = \"%d/%m/%Y\"\nhistory_depth = 5\n[colours]\nhigh = \"magenta\"\n";
+        let config = parse(file, &[("history_depth", "7")]).unwrap();
+        assert_eq!(config.get("date_format"), Some(("%d/%m/%Y".to_string(), Source::File)));
+        assert_eq!(config.get("history_depth"), Some(("7".to_string(), Source::Environment)));
+        assert_eq!(config.get("lock_timeout"), Some(("10".to_string(), Source::Default)));
+        assert_eq!(config.get("sort"), None);
+        assert_eq!(config.appearance().high, Some(Colour::Magenta));
+
+        assert!(parse("sort = \"size\"", &[]).unwrap_err().to_string().contains("sort"));
+        assert!(parse("colour = \"sometimes\"", &[]).is_err());
+        assert!(parse("", &[("colours_low", "plaid")]).is_err());
+        assert!(parse("unknown = 1", &[]).is_err());
+        for timeout in ["-1", "NaN", "inf", "1e300"] {
+            assert!(parse("", &[("lock_timeout", timeout)]).is_err(), "{}", timeout);
+        }
+    }
+
+    #[test]
+    fn aliases_expand_in_place_of_the_subcommand() {
+        let config = parse("[aliases]\ntoday = \"list 'due <= today' --sort due\"\n", &[("aliases_w", "list +work")]).unwrap();
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            config.expand_alias(&args(&["rj", "-j", "x.json", "today", "-a"])),
+            Some(args(&["rj", "-j", "x.json", "list", "due <= today", "--sort", "due", "-a"]))
+        );
+        assert_eq!(config.expand_alias(&args(&["rj", "w"])), Some(args(&["rj", "list", "+work"])));
+        assert_eq!(config.expand_alias(&args(&["rj", "list"])), None);
+    }
+
+    #[test]
+    fn set_keeps_the_rest_of_the_file() {
+        let path = env::temp_dir().join(format!("rusty-journal-config-{}.toml", std::process::id()));
+        let config = Config::from_parts(
+            Some(path.clone()),
+            "# My settings\nsort = \"due\" # soonest first\n",
+            std::iter::empty(),
+        )
+        .unwrap();
+        set(&config, "colours.high", "blue").unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(written, "# My settings\nsort = \"due\" # soonest first\n\n[colours]\nhigh = \"blue\"\n");
+        assert!(set(&config, "history_depth", "many").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Dates written the way people say them, in English or Portuguese.
+//!
+//! Besides `YYYY-MM-DD`, these are understood, relative to today:
+//!
+//! * `today`, `tomorrow`, `yesterday`, `day after tomorrow`
+//!   (`hoje`, `amanhã`, `ontem`, `depois de amanhã`)
+//! * a weekday such as `fri` or `friday` (`sex`, `sexta`, `sexta-feira`): the
+//!   next one after today, as is `next friday` (`próxima sexta`, `sexta que vem`).
+//!   `this friday` (`esta sexta`) may be today.
+//! * `next week`, `next month`, `next year`: the first day of it
+//!   (`próxima semana`, `mês que vem`, ...)
+//! * `in 3 days`, `in 2 weeks`, `in a month` (`em 3 dias`, `daqui a 2 semanas`),
+//!   or the short forms `3d`, `2w`, `1m` and `1y`
+//! * `eow`, `eom`, `eoy` and `end of month` (`fim do mês`): the last day of the
+//!   week (Sunday), month or year
+//!
+//! Case and accents don't matter, so `amanha` works as well as `Amanhã`.
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Read a date for a command-line option, relative to today.
+pub fn parse_due(s: &str) -> Result<NaiveDate, String> {
+    parse(s, Local::now().date_naive()).ok_or_else(|| {
+        format!(
+            "Unknown date '{}', expected e.g. 2026-11-01, tomorrow, fri, in 3 days or eom",
+            s
+        )
+    })
+}
+
+/// Read `text` as a date, relative to `today`.
+pub fn parse(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let folded: String = text
+        .nfd()
+        .filter(|&c| !is_combining_mark(c))
+        .flat_map(char::to_lowercase)
+        .collect();
+    let words: Vec<&str> = folded.split_whitespace().collect();
+
+    match words.as_slice() {
+        [word] => parse_word(word, today),
+        ["day", "after", "tomorrow"] | ["depois", "de", "amanha"] => today.checked_add_days(Days::new(2)),
+        ["next" | "proxima" | "proximo", what] | [what, "que", "vem"] => match (weekday(what), unit(what)) {
+            (Some(day), _) => Some(next_weekday(today, day)),
+            (_, Some(unit)) => Some(start_of_next(today, unit)),
+            _ => None,
+        },
+        ["this" | "esta" | "este", day] => {
+            let day = weekday(day)?;
+            Some(next_weekday(today - Days::new(1), day))
+        }
+        ["in" | "em" | "daqui", count, unit] | ["daqui", "a", count, unit] => {
+            add(today, number(count)?, self::unit(unit)?)
+        }
+        ["end", "of", unit] | ["fim", "do" | "da", unit] => Some(end_of(today, self::unit(unit)?)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+fn parse_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" | "hoje" | "eod" => return Some(today),
+        "tomorrow" | "amanha" => return today.checked_add_days(Days::new(1)),
+        "yesterday" | "ontem" => return today.checked_sub_days(Days::new(1)),
+        "eow" => return Some(end_of(today, Unit::Week)),
+        "eom" => return Some(end_of(today, Unit::Month)),
+        "eoy" => return Some(end_of(today, Unit::Year)),
+        _ => {}
+    }
+    if let Some(day) = weekday(word) {
+        return Some(next_weekday(today, day));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some(date);
+    }
+    // Short offsets such as 3d or +2w.
+    let word = word.strip_prefix('+').unwrap_or(word);
+    let split = word.find(|c: char| !c.is_ascii_digit())?;
+    let unit = match &word[split..] {
+        "d" => Unit::Day,
+        "w" => Unit::Week,
+        "m" => Unit::Month,
+        "y" => Unit::Year,
+        _ => return None,
+    };
+    add(today, word[..split].parse().ok()?, unit)
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let word = word.strip_suffix("-feira").unwrap_or(word);
+    let day = match word {
+        "mon" | "monday" | "seg" | "segunda" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" | "ter" | "terca" => Weekday::Tue,
+        "wed" | "wednesday" | "qua" | "quarta" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" | "qui" | "quinta" => Weekday::Thu,
+        "fri" | "friday" | "sex" | "sexta" => Weekday::Fri,
+        "sat" | "saturday" | "sab" | "sabado" => Weekday::Sat,
+        "sun" | "sunday" | "dom" | "domingo" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(day)
+}
+
+fn unit(word: &str) -> Option<Unit> {
+    let unit = match word {
+        "day" | "days" | "dia" | "dias" => Unit::Day,
+        "week" | "weeks" | "semana" | "semanas" => Unit::Week,
+        "month" | "months" | "mes" | "meses" => Unit::Month,
+        "year" | "years" | "ano" | "anos" => Unit::Year,
+        _ => return None,
+    };
+    Some(unit)
+}
+
+fn number(word: &str) -> Option<u32> {
+    match word {
+        "a" | "an" | "one" | "um" | "uma" => Some(1),
+        _ => word.parse().ok(),
+    }
+}
+
+fn add(date: NaiveDate, count: u32, unit: Unit) -> Option<NaiveDate> {
+    match unit {
+        Unit::Day => date.checked_add_days(Days::new(count.into())),
+        Unit::Week => date.checked_add_days(Days::new(u64::from(count) * 7)),
+        Unit::Month => date.checked_add_months(Months::new(count)),
+        Unit::Year => date.checked_add_months(Months::new(count.checked_mul(12)?)),
+    }
+}
+
+/// The first `day` after `today`.
+fn next_weekday(today: NaiveDate, day: Weekday) -> NaiveDate {
+    let ahead = (day.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7 + 1;
+    today + Days::new(ahead.into())
+}
+
+/// The first day of the week (Monday), month or year after this one.
+fn start_of_next(today: NaiveDate, unit: Unit) -> NaiveDate {
+    end_of(today, unit) + Days::new(1)
+}
+
+/// The last day of the week (Sunday), month or year `today` is in.
+fn end_of(today: NaiveDate, unit: Unit) -> NaiveDate {
+    match unit {
+        Unit::Day => today,
+        Unit::Week => today + Days::new((6 - today.weekday().num_days_from_monday()).into()),
+        Unit::Month => {
+            let first = today.with_day(1).expect("every month has a first day");
+            first + Months::new(1) - Days::new(1)
+        }
+        Unit::Year => NaiveDate::from_ymd_opt(today.year(), 12, 31).expect("every year has a last day"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn english_and_portuguese_agree() {
+        // A Sunday.
+        let today = date("2026-10-18");
+        let same = [
+            ("tomorrow", "Amanhã", "2026-10-19"),
+            ("day after tomorrow", "depois de amanha", "2026-10-20"),
+            ("fri", "sexta-feira", "2026-10-23"),
+            ("next monday", "segunda que vem", "2026-10-19"),
+            ("next week", "próxima semana", "2026-10-19"),
+            ("next month", "mês que vem", "2026-11-01"),
+            ("in 3 days", "daqui a 3 dias", "2026-10-21"),
+            ("in a month", "em um mês", "2026-11-18"),
+            ("eom", "fim do mês", "2026-10-31"),
+            ("end of year", "fim do ano", "2026-12-31"),
+        ];
+        for (english, portuguese, expected) in same {
+            assert_eq!(parse(english, today), Some(date(expected)), "{}", english);
+            assert_eq!(parse(portuguese, today), Some(date(expected)), "{}", portuguese);
+        }
+    }
+
+    #[test]
+    fn weekdays_and_offsets() {
+        // A Friday.
+        let today = date("2026-10-23");
+        assert_eq!(parse("fri", today), Some(date("2026-10-30")));
+        assert_eq!(parse("this friday", today), Some(today));
+        assert_eq!(parse("eow", today), Some(date("2026-10-25")));
+        assert_eq!(parse("2w", today), Some(date("2026-11-06")));
+        assert_eq!(parse("+1m", date("2026-01-31")), Some(date("2026-02-28")));
+        assert_eq!(parse("2026-12-01", today), Some(date("2026-12-01")));
+        assert_eq!(parse("someday", today), None);
+        assert_eq!(parse("in 3 fortnights", today), None);
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, Error, ErrorKind, Result, Write};
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+/// Let the user change `text` in their editor, and return what they saved.
+///
+/// The editor comes from `$VISUAL` or `$EDITOR`, falling back to `vi`. It may
+/// include arguments, such as `code --wait`. `extension` is given to the
+/// temporary file so the editor can pick the right syntax highlighting.
+pub fn edit(text: &str, extension: &str) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let file = TempFile::create(text, extension)?;
+    let status = Command::new(program).args(words).arg(&file.0).status();
+    let edited = fs::read_to_string(&file.0);
+    drop(file);
+
+    let status = status.map_err(|e| Error::new(e.kind(), format!("Couldn't run the editor '{}': {}", editor, e)))?;
+    if !status.success() {
+        return Err(Error::other(format!("The editor '{}' failed ({})", editor, status)));
+    }
+    edited
+}
+
+/// A file in the temporary directory that only we can read, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Create a new file with `text` in it. Its name is random and it must not
+    /// exist yet, so another user can't have put a file or a symlink there first.
+    fn create(text: &str, extension: &str) -> Result<TempFile> {
+        for _ in 0..100 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(process::id());
+            let name = format!("rusty-journal-{:016x}.{}", hasher.finish(), extension);
+            let path = env::temp_dir().join(name);
+
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(mut file) => {
+                    let temp = TempFile(path);
+                    file.write_all(text.as_bytes())?;
+                    return Ok(temp);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::new(ErrorKind::AlreadyExists, "Couldn't create a temporary file to edit"))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Ask a yes/no question on the terminal; yes unless the answer starts with `n`.
+pub fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [Y/n] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "No answer given"));
+    }
+    Ok(!answer.trim().to_lowercase().starts_with('n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_files_are_private_and_removed() {
+        let first = TempFile::create("one", "json").unwrap();
+        let second = TempFile::create("two", "json").unwrap();
+        assert_ne!(first.0, second.0);
+        assert_eq!(fs::read_to_string(&first.0).unwrap(), "one");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&first.0).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let path = first.0.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,162 @@
+//! The dependency graph in Graphviz DOT, e.g.
+//!
+//! ```text
+//! rusty-journal graph | dot -Tsvg > graph.svg
+//! ```
+//!
+//! Arrows point from a task to the tasks waiting on it, so they follow the
+//! order work has to happen in. The longest chain of open tasks, the critical
+//! path, is drawn in red.
+
+use crate::storage::Storage;
+use crate::tasks::{Journal, Task};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::io::Result;
+
+pub fn print_graph(storage: &mut dyn Storage, all: bool) -> Result<()> {
+    let journal = storage.load()?;
+    print!("{}", to_dot(&journal, all));
+    Ok(())
+}
+
+/// Render the tasks that take part in a dependency. Completed tasks are only
+/// included with `all`, and drawn greyed out.
+fn to_dot(journal: &Journal, all: bool) -> String {
+    let shown: HashMap<u64, &Task> = journal
+        .tasks
+        .iter()
+        .filter(|task| all || !task.is_completed())
+        .map(|task| (task.id, task))
+        .collect();
+    let edges: Vec<(u64, u64)> = journal
+        .tasks
+        .iter()
+        .filter(|task| shown.contains_key(&task.id))
+        .flat_map(|task| task.depends.iter().map(move |&dependency| (dependency, task.id)))
+        .filter(|(dependency, _)| shown.contains_key(dependency))
+        .collect();
+    let linked: HashSet<u64> = edges.iter().flat_map(|&(from, to)| [from, to]).collect();
+
+    let critical = critical_path(journal);
+    let on_path: HashSet<(u64, u64)> = critical.windows(2).map(|pair| (pair[0], pair[1])).collect();
+
+    let mut dot = String::from("digraph journal {\n    rankdir=LR;\n    node [shape=box];\n");
+    // Keep the journal's order so the output is stable.
+    for task in journal.tasks.iter().filter(|task| linked.contains(&task.id)) {
+        let mut label = format!("#{} {}", task.id, task.text);
+        if let Some(due) = task.due {
+            write!(label, "\ndue {}", due).unwrap();
+        }
+        let style = if task.is_completed() {
+            ", style=dashed, color=gray, fontcolor=gray"
+        } else if critical.contains(&task.id) {
+            ", color=red, penwidth=2"
+        } else {
+            ""
+        };
+        writeln!(dot, "    t{} [label={}{}];", task.id, quote(&label), style).unwrap();
+    }
+    for (from, to) in edges {
+        let style = if on_path.contains(&(from, to)) { " [color=red, penwidth=2]" } else { "" };
+        writeln!(dot, "    t{} -> t{}{};", from, to, style).unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// The longest chain of open tasks where each depends on the one before,
+/// first to last. Empty if no open task depends on another.
+fn critical_path(journal: &Journal) -> Vec<u64> {
+    let open = journal.open_ids();
+    let depends: HashMap<u64, Vec<u64>> = journal
+        .tasks
+        .iter()
+        .filter(|task| open.contains(&task.id))
+        .map(|task| (task.id, task.open_dependencies(&open).collect()))
+        .collect();
+
+    // The length of the longest chain ending at each task, and the task
+    // before it in that chain.
+    let mut longest: HashMap<u64, (usize, Option<u64>)> = HashMap::new();
+    for task in journal.tasks.iter().filter(|task| open.contains(&task.id)) {
+        chain_length(task.id, &depends, &mut longest, &mut HashSet::new());
+    }
+
+    let Some((&end, &(length, _))) = longest
+        .iter()
+        .max_by_key(|(id, (length, _))| (*length, std::cmp::Reverse(**id)))
+    else {
+        return Vec::new();
+    };
+    if length < 2 {
+        return Vec::new();
+    }
+    let mut path = vec![end];
+    while let Some((_, Some(previous))) = longest.get(path.last().unwrap()) {
+        path.push(*previous);
+    }
+    path.reverse();
+    path
+}
+
+fn chain_length(
+    id: u64,
+    depends: &HashMap<u64, Vec<u64>>,
+    longest: &mut HashMap<u64, (usize, Option<u64>)>,
+    visiting: &mut HashSet<u64>,
+) -> usize {
+    if let Some(&(length, _)) = longest.get(&id) {
+        return length;
+    }
+    // `depends` refuses cycles, but a hand-edited journal could still have one.
+    if !visiting.insert(id) {
+        return 0;
+    }
+    let mut best = (1, None);
+    for &dependency in depends.get(&id).into_iter().flatten() {
+        let length = chain_length(dependency, depends, longest, visiting) + 1;
+        if length > best.0 {
+            best = (length, Some(dependency));
+        }
+    }
+    visiting.remove(&id);
+    longest.insert(id, best);
+    best.0
+}
+
+/// A DOT string literal.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_the_longest_open_chain() {
+        let mut journal = Journal::default();
+        for text in ["design", "build", "ship", "write \"docs\""] {
+            journal.push(Task::new(text.into()));
+        }
+        journal.tasks[1].depends.insert(1);
+        journal.tasks[2].depends.extend([2, 4]);
+
+        assert_eq!(critical_path(&journal), vec![1, 2, 3]);
+        let dot = to_dot(&journal, false);
+        assert!(dot.contains("t1 -> t2 [color=red, penwidth=2];"));
+        assert!(dot.contains("t4 -> t3;\n"));
+        assert!(dot.contains(r##"label="#4 write \"docs\"""##));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::storage::Storage;
+use crate::tasks::{Journal, Task};
+use chrono::{serde::ts_seconds, DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Error, ErrorKind, Result};
+
+/// The operation log kept next to the journal, used by `undo` and `redo`.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct History {
+    /// Operations that can be undone, oldest first.
+    pub done: Vec<Operation>,
+    /// Operations that were undone and can be redone, most recently undone last.
+    pub undone: Vec<Operation>,
+}
+
+/// One mutating command, recorded as the tasks it changed.
+///
+/// Tasks are matched by ID, so undoing works however the journal has been
+/// sorted or filtered since.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Operation {
+    #[serde(with = "ts_seconds")]
+    pub at: DateTime<Utc>,
+    pub description: String,
+    pub changes: Vec<Change>,
+    /// The journal's `next_id` afterwards. Undo never lowers it, so IDs stay
+    /// unique even across undone adds.
+    pub next_id: u64,
+    /// The order of all task IDs before and after, only kept when the command
+    /// reordered tasks rather than just adding or removing some.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<(Vec<u64>, Vec<u64>)>,
+}
+
+/// A task before and after an operation, with its position in the journal.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Change {
+    pub id: u64,
+    pub before: Side,
+    pub after: Side,
+}
+
+/// A task and its position on one side of a change; `None` if it didn't exist.
+pub type Side = Option<(usize, Task)>;
+
+impl Operation {
+    /// Work out what changed between two versions of the journal. Returns
+    /// `None` if nothing did.
+    pub fn between(label: &str, before: &Journal, after: &Journal) -> Option<Operation> {
+        let index = |journal: &Journal| -> HashMap<u64, usize> {
+            journal.tasks.iter().enumerate().map(|(position, task)| (task.id, position)).collect()
+        };
+        let (before_index, after_index) = (index(before), index(after));
+
+        let ids: BTreeSet<u64> = before_index.keys().chain(after_index.keys()).copied().collect();
+        let changes: Vec<Change> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let old = before_index.get(&id).map(|&position| (position, before.tasks[position].clone()));
+                let new = after_index.get(&id).map(|&position| (position, after.tasks[position].clone()));
+                let same = matches!((&old, &new), (Some((_, a)), Some((_, b))) if a == b);
+                (!same).then_some(Change { id, before: old, after: new })
+            })
+            .collect();
+
+        // Tasks that exist on both sides should keep their relative order,
+        // unless the command moved something.
+        let kept = |journal: &Journal, other: &HashMap<u64, usize>| -> Vec<u64> {
+            journal.tasks.iter().map(|task| task.id).filter(|id| other.contains_key(id)).collect()
+        };
+        let order = if kept(before, &after_index) != kept(after, &before_index) {
+            let ids = |journal: &Journal| journal.tasks.iter().map(|task| task.id).collect();
+            Some((ids(before), ids(after)))
+        } else {
+            None
+        };
+
+        if changes.is_empty() && order.is_none() && before.next_id == after.next_id {
+            return None;
+        }
+
+        Some(Operation {
+            at: Utc::now(),
+            description: describe(label, &changes),
+            changes,
+            next_id: after.next_id,
+            order,
+        })
+    }
+
+    /// Put the journal back the way it was before the operation.
+    fn revert(&self, journal: &mut Journal) -> Result<()> {
+        self.apply(journal, false)
+    }
+
+    /// Do the operation again.
+    fn replay(&self, journal: &mut Journal) -> Result<()> {
+        self.apply(journal, true)
+    }
+
+    fn apply(&self, journal: &mut Journal, forward: bool) -> Result<()> {
+        // Refuse if the tasks no longer look the way this operation left them,
+        // e.g. because the journal was edited by hand in between.
+        for change in &self.changes {
+            let (from, _) = change.sides(forward);
+            let current = journal.tasks.iter().find(|task| task.id == change.id);
+            if current != from.as_ref().map(|(_, task)| task) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Task {} changed since '{}'; can't safely undo or redo it", change.id, self.description),
+                ));
+            }
+        }
+
+        // Take out every task the operation touched, then put back the
+        // wanted versions in position order.
+        journal.tasks.retain(|task| !self.changes.iter().any(|change| change.id == task.id));
+        let mut wanted: Vec<&(usize, Task)> = self
+            .changes
+            .iter()
+            .filter_map(|change| change.sides(forward).1.as_ref())
+            .collect();
+        wanted.sort_by_key(|(position, _)| *position);
+        for (position, task) in wanted {
+            let position = (*position).min(journal.tasks.len());
+            journal.tasks.insert(position, task.clone());
+        }
+
+        if let Some((before, after)) = &self.order {
+            let order = if forward { after } else { before };
+            let rank: HashMap<u64, usize> = order.iter().enumerate().map(|(rank, id)| (*id, rank)).collect();
+            journal.tasks.sort_by_key(|task| rank.get(&task.id).copied().unwrap_or(usize::MAX));
+        }
+
+        journal.next_id = journal.next_id.max(self.next_id);
+        Ok(())
+    }
+}
+
+impl Change {
+    /// The side to start from and the side to end up with, when going forward
+    /// (redo) or backward (undo).
+    fn sides(&self, forward: bool) -> (&Side, &Side) {
+        if forward {
+            (&self.before, &self.after)
+        } else {
+            (&self.after, &self.before)
+        }
+    }
+}
+
+/// A short summary such as `done: completed #3 "water the plants"`.
+fn describe(label: &str, changes: &[Change]) -> String {
+    let parts: Vec<String> = changes
+        .iter()
+        .map(|change| {
+            let (verb, task) = match (&change.before, &change.after) {
+                (None, Some((_, task))) => ("added", task),
+                (Some((_, task)), None) => ("removed", task),
+                (Some((_, old)), Some((_, new))) if !old.is_completed() && new.is_completed() => {
+                    ("completed", new)
+                }
+                (_, Some((_, task))) => ("changed", task),
+                (None, None) => unreachable!("a change always has a side"),
+            };
+            format!("{} #{} \"{}\"", verb, change.id, task.text)
+        })
+        .collect();
+
+    match parts.len() {
+        0 => format!("{}: reordered tasks", label),
+        1..=3 => format!("{}: {}", label, parts.join(", ")),
+        n => format!("{}: {}, and {} more", label, parts[..2].join(", "), n - 2),
+    }
+}
+
+/// Wraps a storage so that every save is recorded in its operation log.
+pub struct Recorder<'a> {
+    inner: &'a mut dyn Storage,
+    label: String,
+    depth: usize,
+    /// The journal as it was last loaded or saved.
+    base: Option<Journal>,
+}
+
+impl<'a> Recorder<'a> {
+    /// Record saves as operations named `label`, keeping at most `depth` of them.
+    pub fn new(inner: &'a mut dyn Storage, label: &str, depth: usize) -> Recorder<'a> {
+        Recorder {
+            inner,
+            label: label.to_string(),
+            depth,
+            base: None,
+        }
+    }
+}
+
+impl Storage for Recorder<'_> {
+    fn load(&mut self) -> Result<Journal> {
+        let journal = self.inner.load()?;
+        self.base = Some(journal.clone());
+        Ok(journal)
+    }
+
+    fn save(&mut self, journal: &Journal) -> Result<()> {
+        self.inner.save(journal)?;
+
+        let before = self.base.replace(journal.clone()).unwrap_or_default();
+        if self.depth > 0 {
+            if let Some(operation) = Operation::between(&self.label, &before, journal) {
+                let mut history = self.inner.load_history()?;
+                history.done.push(operation);
+                let excess = history.done.len().saturating_sub(self.depth);
+                history.done.drain(..excess);
+                // A new change makes the undone operations unreachable.
+                history.undone.clear();
+                self.inner.save_history(&history)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_history(&mut self) -> Result<History> {
+        self.inner.load_history()
+    }
+
+    fn save_history(&mut self, history: &History) -> Result<()> {
+        self.inner.save_history(history)
+    }
+}
+
+/// Undo the last `count` operations.
+pub fn undo(storage: &mut dyn Storage, count: usize) -> Result<()> {
+    step(storage, count, false)
+}
+
+/// Redo the last `count` undone operations.
+pub fn redo(storage: &mut dyn Storage, count: usize) -> Result<()> {
+    step(storage, count, true)
+}
+
+/// Apply all `count` steps to the journal in memory and save it with the
+/// history once, so either all of them happen or, if one fails, none do.
+/// Nothing is printed until both are saved.
+fn step(storage: &mut dyn Storage, count: usize, forward: bool) -> Result<()> {
+    let mut journal = storage.load()?;
+    let mut history = storage.load_history()?;
+
+    let mut messages = Vec::new();
+    for _ in 0..count {
+        let (source, target) = if forward {
+            (&mut history.undone, &mut history.done)
+        } else {
+            (&mut history.done, &mut history.undone)
+        };
+        let Some(operation) = source.pop() else {
+            messages.push(if forward { "Nothing to redo.".to_string() } else { "Nothing to undo.".to_string() });
+            break;
+        };
+
+        if forward {
+            operation.replay(&mut journal)?;
+            messages.push(format!("Redid {}", operation.description));
+        } else {
+            operation.revert(&mut journal)?;
+            messages.push(format!("Undid {}", operation.description));
+        }
+        target.push(operation);
+    }
+
+    storage.save_with_history(&journal, &history)?;
+    for message in messages {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+/// Show recent operations, newest first, including ones that can be redone.
+pub fn list_history(storage: &mut dyn Storage) -> Result<()> {
+    let history = storage.load_history()?;
+    if history.done.is_empty() && history.undone.is_empty() {
+        println!("No operations recorded yet.");
+        return Ok(());
+    }
+
+    for operation in history.undone.iter() {
+        print_operation("redo", operation);
+    }
+    for operation in history.done.iter().rev() {
+        print_operation("undo", operation);
+    }
+    Ok(())
+}
+
+fn print_operation(kind: &str, operation: &Operation) {
+    let at = operation.at.with_timezone(&Local).format("%F %H:%M:%S");
+    println!("{:<4}  {}  {}", kind, at, operation.description);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+    use crate::tasks::{self, Task};
+
+    fn snapshot(storage: &mut dyn Storage) -> serde_json::Value {
+        serde_json::to_value(storage.load().unwrap().tasks).unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo_restore_each_state() {
+        let mut store = MemoryStore::default();
+        let mut states = vec![snapshot(&mut store)];
+
+        tasks::add_task(&mut Recorder::new(&mut store, "add", 10), Task::new("one".into())).unwrap();
+        states.push(snapshot(&mut store));
+        tasks::add_task(&mut Recorder::new(&mut store, "add", 10), Task::new("two".into())).unwrap();
+        states.push(snapshot(&mut store));
+        tasks::complete_task(&mut Recorder::new(&mut store, "done", 10), 1, false).unwrap();
+        states.push(snapshot(&mut store));
+        tasks::purge_tasks(&mut Recorder::new(&mut store, "purge", 10), &[]).unwrap();
+        states.push(snapshot(&mut store));
+
+        for expected in states.iter().rev().skip(1) {
+            undo(&mut store, 1).unwrap();
+            assert_eq!(&snapshot(&mut store), expected);
+        }
+        redo(&mut store, 4).unwrap();
+        assert_eq!(&snapshot(&mut store), states.last().unwrap());
+
+        // Undoing an add doesn't free its ID.
+        undo(&mut store, 4).unwrap();
+        assert_eq!(store.load().unwrap().next_id, 3);
+    }
+
+    #[test]
+    fn history_depth_is_capped() {
+        let mut store = MemoryStore::default();
+        for text in ["a", "b", "c"] {
+            tasks::add_task(&mut Recorder::new(&mut store, "add", 2), Task::new(text.into())).unwrap();
+        }
+        assert_eq!(store.load_history().unwrap().done.len(), 2);
+    }
+
+    #[test]
+    fn undoing_several_is_all_or_nothing() {
+        let mut store = MemoryStore::default();
+        for text in ["one", "two"] {
+            tasks::add_task(&mut Recorder::new(&mut store, "add", 10), Task::new(text.into())).unwrap();
+        }
+        // Changed behind the history's back, so the first add can't be undone.
+        let mut journal = store.load().unwrap();
+        journal.tasks[0].text = "one, edited".into();
+        store.save(&journal).unwrap();
+
+        assert!(undo(&mut store, 2).is_err());
+        assert_eq!(store.load().unwrap().tasks.len(), 2);
+        assert_eq!(store.load_history().unwrap().done.len(), 2);
+        undo(&mut store, 1).unwrap();
+        assert_eq!(store.load().unwrap().tasks.len(), 1);
+    }
+
+    /// A store that can't save its history.
+    struct NoHistory(MemoryStore);
+
+    impl Storage for NoHistory {
+        fn load(&mut self) -> Result<Journal> {
+            self.0.load()
+        }
+        fn save(&mut self, journal: &Journal) -> Result<()> {
+            self.0.save(journal)
+        }
+        fn load_history(&mut self) -> Result<History> {
+            self.0.load_history()
+        }
+        fn save_history(&mut self, _: &History) -> Result<()> {
+            Err(Error::other("disk full"))
+        }
+    }
+
+    #[test]
+    fn a_failed_history_save_keeps_the_journal() {
+        let mut store = MemoryStore::default();
+        tasks::add_task(&mut Recorder::new(&mut store, "add", 10), Task::new("one".into())).unwrap();
+
+        let mut failing = NoHistory(store);
+        assert!(undo(&mut failing, 1).is_err());
+        assert_eq!(failing.load().unwrap().tasks.len(), 1);
+        undo(&mut failing.0, 1).unwrap();
+        assert!(failing.0.load().unwrap().tasks.is_empty());
+    }
+}
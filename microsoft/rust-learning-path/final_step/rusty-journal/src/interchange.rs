@@ -0,0 +1,92 @@
+use crate::storage::Storage;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::str::FromStr;
+
+mod ics;
+mod todotxt;
+
+/// The file formats tasks can be imported from and exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    TodoTxt,
+    Ics,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "todotxt" | "todo.txt" => Ok(Format::TodoTxt),
+            "ics" | "ical" | "icalendar" => Ok(Format::Ics),
+            _ => Err(format!("Unknown format '{}', expected todotxt or ics", s)),
+        }
+    }
+}
+
+/// Add the tasks in the file at `path` to the journal.
+///
+/// todo.txt lines always become new tasks. iCalendar VTODOs are merged by UID,
+/// so importing a calendar again updates the tasks it added last time.
+pub fn import(storage: &mut dyn Storage, format: Format, path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let tasks = match format {
+        Format::TodoTxt => todotxt::parse(&text),
+        Format::Ics => ics::parse(&text),
+    }
+    .map_err(|message| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), message)))?;
+
+    let mut journal = storage.load()?;
+    let (ids, added, updated) = match format {
+        Format::TodoTxt => {
+            let ids: Vec<u64> = tasks.into_iter().map(|task| journal.push(task)).collect();
+            let added = ids.len();
+            (ids, added, 0)
+        }
+        Format::Ics => ics::merge(&mut journal, tasks)?,
+    };
+    for &id in &ids {
+        journal.check_task(id)?;
+    }
+    storage.save(&journal)?;
+
+    match format {
+        Format::TodoTxt => println!("Imported {} task(s).", added),
+        Format::Ics => println!("Imported {} new task(s) and updated {}.", added, updated),
+    }
+    Ok(())
+}
+
+/// Write every task in the journal, completed ones included, to `output` or
+/// to stdout without it.
+pub fn export(storage: &mut dyn Storage, format: Format, output: Option<&Path>) -> Result<()> {
+    let journal = storage.load()?;
+    let text = match format {
+        Format::TodoTxt => todotxt::format(&journal.tasks),
+        Format::Ics => ics::format(&journal.tasks, Utc::now()),
+    };
+
+    match output {
+        Some(path) => {
+            fs::write(path, text)?;
+            println!("Exported {} task(s) to {}.", journal.tasks.len(), path.display());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// The start of a day in local time, for formats that only store dates.
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .map_or_else(|| date.and_time(NaiveTime::MIN).and_utc(), |at| at.with_timezone(&Utc))
+}
+
+fn local_date(at: DateTime<Utc>) -> NaiveDate {
+    at.with_timezone(&Local).date_naive()
+}
@@ -0,0 +1,485 @@
+//! iCalendar (RFC 5545) files of VTODO components, which calendar apps show
+//! as to-dos or reminders.
+//!
+//! Each task maps to one VTODO: its text to `SUMMARY`, tags to `CATEGORIES`,
+//! and its dates, priority and recurrence to the matching properties. The
+//! project has no standard property, so it goes in `X-RUSTY-JOURNAL-PROJECT`.
+//!
+//! Times written with a `TZID` are read as local times, and recurrence rules
+//! the journal can't represent are kept as they are and written back out.
+
+use super::{local_date, local_midnight};
+use crate::tasks::{parse_tag, Journal, Priority, Status, Task};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::HashMap;
+
+/// The longest a line may be, in octets, before it has to be folded.
+const LINE_LIMIT: usize = 75;
+
+/// Where a task's unsupported `RRULE` is kept, in `Task::extensions`.
+const RAW_RRULE: &str = "rrule";
+
+/// Write tasks as a VCALENDAR with one VTODO each, stamped with `now`.
+pub fn format(tasks: &[Task], now: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    let mut line = |line: String| fold(&line, &mut ics);
+
+    line("BEGIN:VCALENDAR".to_string());
+    line("VERSION:2.0".to_string());
+    line(format!("PRODID:-//rusty-journal//rusty-journal {}//EN", env!("CARGO_PKG_VERSION")));
+    for task in tasks {
+        line("BEGIN:VTODO".to_string());
+        line(format!("UID:{}", escape(&task.uid())));
+        line(format!("DTSTAMP:{}", utc_time(now)));
+        line(format!("CREATED:{}", utc_time(task.created_at)));
+        line(format!("SUMMARY:{}", escape(&task.text)));
+        match task.status {
+            Status::Pending => line("STATUS:NEEDS-ACTION".to_string()),
+            Status::Completed => line("STATUS:COMPLETED".to_string()),
+        }
+        if let Some(completed_at) = task.completed_at {
+            line(format!("COMPLETED:{}", utc_time(completed_at)));
+        }
+        if let Some(due) = task.due {
+            // Recurrences count from DTSTART, and the journal counts from the due date.
+            if task.recurrence.is_some() || task.extensions.contains_key(RAW_RRULE) {
+                line(format!("DTSTART;VALUE=DATE:{}", due.format("%Y%m%d")));
+            }
+            line(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+        }
+        if let Some(priority) = task.priority {
+            let value = match priority {
+                Priority::High => 1,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+            };
+            line(format!("PRIORITY:{}", value));
+        }
+        if !task.tags.is_empty() {
+            let tags: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+            line(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(project) = &task.project {
+            line(format!("X-RUSTY-JOURNAL-PROJECT:{}", escape(project)));
+        }
+        match (&task.recurrence, task.extensions.get(RAW_RRULE)) {
+            (Some(recurrence), _) => line(format!("RRULE:{}", recurrence.to_rrule())),
+            (None, Some(rule)) => line(format!("RRULE:{}", rule)),
+            (None, None) => {}
+        }
+        line("END:VTODO".to_string());
+    }
+    line("END:VCALENDAR".to_string());
+    ics
+}
+
+/// Read the VTODOs in an iCalendar file. Each task's `uid` is set to the
+/// VTODO's UID. Other components, such as events, are skipped.
+pub fn parse(text: &str) -> Result<Vec<Task>, String> {
+    let mut tasks = Vec::new();
+    // The task being read, and how many components deep inside it we are, so
+    // that e.g. a VALARM's properties aren't taken for the task's.
+    let mut current: Option<(Task, usize)> = None;
+
+    for (number, line) in unfold(text) {
+        let error = |message: String| format!("line {}: {}", number, message);
+        let (name, value) = split_line(&line).ok_or_else(|| error(format!("expected NAME:value, found '{}'", line)))?;
+
+        match (name.as_str(), &mut current) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some((Task::new(String::new()), 0));
+            }
+            ("BEGIN", Some((_, depth))) => *depth += 1,
+            ("END", Some((_, depth))) if *depth > 0 => *depth -= 1,
+            ("END", Some(_)) => {
+                let (mut task, _) = current.take().unwrap();
+                if task.uid.is_none() {
+                    return Err(error("a VTODO has no UID".to_string()));
+                }
+                // STATUS wins over a COMPLETED date that disagrees with it.
+                if task.is_completed() && task.completed_at.is_none() {
+                    task.completed_at = Some(Utc::now());
+                } else if !task.is_completed() {
+                    task.completed_at = None;
+                }
+                tasks.push(task);
+            }
+            (_, Some((task, 0))) => read_property(task, &name, &value).map_err(error)?,
+            _ => {}
+        }
+    }
+    if current.is_some() {
+        return Err("the file ends inside a VTODO".to_string());
+    }
+    Ok(tasks)
+}
+
+/// Set the task field a VTODO property maps to. Dates and date-times are told
+/// apart by their length, so the `VALUE` parameter isn't needed.
+fn read_property(task: &mut Task, name: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("invalid {} '{}'", name, value);
+    match name {
+        "UID" => task.uid = Some(unescape(value)),
+        "SUMMARY" => task.text = unescape(value),
+        "CREATED" => task.created_at = parse_time(value).ok_or_else(invalid)?,
+        "COMPLETED" => task.completed_at = Some(parse_time(value).ok_or_else(invalid)?),
+        "DUE" => task.due = Some(parse_time(value).map(local_date).ok_or_else(invalid)?),
+        "STATUS" => {
+            task.status = match value.to_uppercase().as_str() {
+                "COMPLETED" | "CANCELLED" => Status::Completed,
+                _ => Status::Pending,
+            }
+        }
+        "PRIORITY" => {
+            task.priority = match value.parse::<u8>().map_err(|_| invalid())? {
+                0 => None,
+                1..=4 => Some(Priority::High),
+                5 => Some(Priority::Medium),
+                _ => Some(Priority::Low),
+            }
+        }
+        "CATEGORIES" => {
+            for category in split_list(value).into_iter().filter(|category| !category.trim().is_empty()) {
+                match category_tag(&category) {
+                    Some(tag) => {
+                        task.tags.insert(tag);
+                    }
+                    None => eprintln!("Warning: skipping the category '{}', which can't be a tag", category),
+                }
+            }
+        }
+        "X-RUSTY-JOURNAL-PROJECT" => task.project = Some(unescape(value)),
+        "RRULE" => match value.parse() {
+            Ok(recurrence) => task.recurrence = Some(recurrence),
+            Err(_) => {
+                task.extensions.insert(RAW_RRULE.to_string(), value.to_string());
+            }
+        },
+        _ => {}
+    }
+    Ok(())
+}
+
+/// A category as a tag: spaces become dashes and leading `+`s go, as a tag
+/// is written `+tag` on the command line. `None` if that still isn't a tag.
+fn category_tag(category: &str) -> Option<String> {
+    let joined = category.split_whitespace().collect::<Vec<_>>().join("-");
+    let tag = parse_tag(joined.trim_start_matches('+')).ok()?;
+    (parse_tag(&tag).as_deref() == Ok(tag.as_str())).then_some(tag)
+}
+
+/// Join folded lines back together, numbering them by where they started.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push((number + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Split `NAME;PARAM=value:VALUE` into its name, upper-cased, and its value.
+/// Colons inside quoted parameter values don't count.
+fn split_line(line: &str) -> Option<(String, String)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(index),
+            _ => {}
+        }
+        None
+    })?;
+    let name = line[..colon].split(';').next()?.trim().to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, line[colon + 1..].to_string()))
+}
+
+/// Merge imported VTODOs into the journal by UID: known tasks are updated and
+/// the rest are added, so a UID that's in the file twice ends up as one task.
+/// Returns the IDs of the tasks touched, how many of them are new and how many
+/// changed.
+///
+/// A task keeps what a VTODO can't express: its ID, parent, dependencies,
+/// extensions and tracked time, and its project when the VTODO doesn't name
+/// one. A task the VTODO marks done is completed the way `done` does it, so
+/// a recurring one comes back with its next due date; one with open subtasks
+/// stays open, with a warning.
+pub fn merge(journal: &mut Journal, tasks: Vec<Task>) -> std::io::Result<(Vec<u64>, usize, usize)> {
+    let mut positions: HashMap<String, usize> = journal
+        .tasks
+        .iter()
+        .enumerate()
+        .map(|(position, task)| (task.uid(), position))
+        .collect();
+
+    let mut ids = Vec::new();
+    let (mut added, mut updated) = (0, 0);
+    for task in tasks {
+        let position = task.uid.as_ref().and_then(|uid| positions.get(uid));
+        match position {
+            Some(&position) => {
+                let id = journal.tasks[position].id;
+                let mut completes = task.is_completed() && !journal.tasks[position].is_completed();
+                if completes {
+                    // `done` refuses these too; one VTODO shouldn't stop the whole import.
+                    let open = journal.open_ids();
+                    let subtasks = journal.descendants(id).into_iter().filter(|id| open.contains(id)).count();
+                    if subtasks > 0 {
+                        eprintln!(
+                            "Warning: task {} has {} open subtask(s), so it stays open although the file has it done",
+                            id, subtasks
+                        );
+                        completes = false;
+                    }
+                }
+                let existing = &mut journal.tasks[position];
+                let completed_at = task.completed_at;
+                let mut extensions = existing.extensions.clone();
+                extensions.remove(RAW_RRULE);
+                extensions.extend(task.extensions);
+                let mut merged = Task {
+                    id: existing.id,
+                    uid: existing.uid.clone(),
+                    parent: existing.parent,
+                    depends: existing.depends.clone(),
+                    project: task.project.or(existing.project.clone()),
+                    extensions,
+                    time: existing.time.clone(),
+                    ..task
+                };
+                // Completing goes through `Journal::complete` below, or not at all.
+                if merged.is_completed() && !existing.is_completed() {
+                    merged.status = existing.status;
+                    merged.completed_at = None;
+                }
+                if merged != *existing || completes {
+                    *existing = merged;
+                    updated += 1;
+                }
+                ids.push(id);
+                if completes {
+                    let completion = journal.complete(id, false)?;
+                    let position = journal.position(id)?;
+                    journal.tasks[position].completed_at = completed_at;
+                    ids.extend(completion.added.iter().map(|&(id, _)| id));
+                }
+            }
+            None => {
+                if let Some(uid) = &task.uid {
+                    positions.insert(uid.clone(), journal.tasks.len());
+                }
+                ids.push(journal.push(task));
+                added += 1;
+            }
+        }
+    }
+    Ok((ids, added, updated))
+}
+
+/// Write one content line, folding it so no line is longer than 75 octets.
+/// Folds never split a UTF-8 character.
+fn fold(line: &str, ics: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Escape a TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    split_list(text).join(",")
+}
+
+/// Split a list of TEXT values on unescaped commas, unescaping each one.
+fn split_list(text: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => items.last_mut().unwrap().push('\n'),
+                Some(c) => items.last_mut().unwrap().push(c),
+                None => items.last_mut().unwrap().push('\\'),
+            },
+            ',' => items.push(String::new()),
+            c => items.last_mut().unwrap().push(c),
+        }
+    }
+    items
+}
+
+/// A DATE or DATE-TIME value. Dates are local midnight, and times without a
+/// trailing `Z` are local times.
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(local_midnight);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok().map(|at| at.and_utc());
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Local.from_local_datetime(&local).earliest().map(|at| at.with_timezone(&Utc))
+}
+
+fn utc_time(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_folding_and_escaping() {
+        let mut task = Task::new("Buy milk, eggs; and a very long list of other things: ção, naïve, 😀 \\ done".into());
+        task.id = 7;
+        task.due = "2026-10-20".parse().ok();
+        task.priority = Some(Priority::Medium);
+        task.tags.insert("home".into());
+        task.tags.insert("errands".into());
+        task.project = Some("house".into());
+        task.recurrence = "weekly:mon,thu".parse().ok();
+        task.complete();
+
+        let ics = format(std::slice::from_ref(&task), Utc::now());
+        assert!(ics.ends_with("\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= LINE_LIMIT && !line.contains('\n')));
+        assert!(ics.contains("\r\n "), "the summary should have been folded");
+        assert!(ics.contains("SUMMARY:Buy milk\\, eggs\\; and"));
+        assert!(ics.contains("CATEGORIES:errands,home\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TH\r\n"));
+
+        let parsed = parse(&ics).unwrap();
+        assert_eq!(parsed.len(), 1);
+        let back = &parsed[0];
+        assert_eq!(back.uid, Some(task.uid()));
+        assert_eq!(back.text, task.text);
+        assert_eq!(back.created_at.timestamp(), task.created_at.timestamp());
+        assert_eq!(back.completed_at.map(|at| at.timestamp()), task.completed_at.map(|at| at.timestamp()));
+        assert_eq!((back.due, back.priority, &back.tags), (task.due, task.priority, &task.tags));
+        assert_eq!((&back.project, &back.recurrence), (&task.project, &task.recurrence));
+    }
+
+    #[test]
+    fn reads_other_clients_todos() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:event\r\nSUMMARY:Not a task\r\nEND:VEVENT\r\n\
+                   BEGIN:VTODO\r\nUID:abc@example.com\r\nSUMMARY:Call\r\n  the bank\r\n\
+                   DUE;TZID=\"Europe/Lisbon\":20261020T170000\r\nPRIORITY:2\r\n\
+                   CATEGORIES:work,Phone calls,+home,+\r\nRRULE:FREQ=MONTHLY;BYDAY=1MO\r\n\
+                   BEGIN:VALARM\r\nSUMMARY:Alarm\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let tasks = parse(ics).unwrap();
+        assert_eq!(tasks.len(), 1);
+        let task = &tasks[0];
+        assert_eq!(task.text, "Call the bank");
+        assert_eq!(task.due, "2026-10-20".parse().ok());
+        assert_eq!(task.priority, Some(Priority::High));
+        assert!(task.tags.contains("Phone-calls"));
+        assert_eq!(task.tags.len(), 3, "+home is home, and + is skipped");
+        assert!(task.tags.contains("home"));
+        assert_eq!(task.recurrence, None);
+        assert_eq!(task.extensions.get(RAW_RRULE).map(String::as_str), Some("FREQ=MONTHLY;BYDAY=1MO"));
+    }
+
+    #[test]
+    fn merges_by_uid() {
+        let mut journal = Journal::default();
+        journal.push(Task::new("water the plants".into()));
+        journal.tasks[0].project = Some("home".into());
+        let mut exported = parse(&format(&journal.tasks, Utc::now())).unwrap();
+        exported[0].text = "water the plants twice".into();
+        exported[0].project = None;
+        let mut other = Task::new("from the phone".into());
+        other.uid = Some("phone-1".into());
+        exported.push(other);
+
+        let (ids, added, updated) = merge(&mut journal, exported).unwrap();
+        assert_eq!((ids, added, updated), (vec![1, 2], 1, 1));
+        assert_eq!(journal.tasks[0].text, "water the plants twice");
+        assert_eq!(journal.tasks[0].project.as_deref(), Some("home"));
+        assert_eq!(journal.tasks[0].uid, None);
+        assert_eq!(journal.tasks[1].uid.as_deref(), Some("phone-1"));
+    }
+
+    #[test]
+    fn imported_completions_work_like_done() {
+        let mut journal = Journal::default();
+        let mut task = Task::new("pay rent".into());
+        task.due = "2026-10-01".parse().ok();
+        task.recurrence = "monthly".parse().ok();
+        task.time.push(crate::timesheet::TimeEntry { start: Utc::now(), end: None });
+        journal.push(task);
+
+        let completed = "STATUS:COMPLETED\r\nCOMPLETED:20261002T100000Z";
+        let exported = format(&journal.tasks, Utc::now()).replace("STATUS:NEEDS-ACTION", completed);
+        let todo = |summary: &str, status: &str| {
+            format!("BEGIN:VTODO\r\nUID:x\r\nSUMMARY:{}\r\n{}\r\nEND:VTODO\r\n", summary, status)
+        };
+        let ics = exported.replace(
+            "END:VCALENDAR",
+            &(todo("new", "STATUS:NEEDS-ACTION") + &todo("new, done", completed) + "END:VCALENDAR"),
+        );
+        let (ids, added, updated) = merge(&mut journal, parse(&ics).unwrap()).unwrap();
+        assert_eq!((ids, added, updated), (vec![1, 2, 3, 3], 1, 2));
+        assert_eq!(journal.tasks.len(), 3, "the second x updates the first");
+
+        let rent = &journal.tasks[0];
+        assert!(rent.is_completed());
+        assert_eq!(rent.completed_at, parse_time("20261002T100000Z"));
+        assert!(rent.time[0].end.is_some(), "the timer stops");
+        assert_eq!(journal.tasks[1].text, "pay rent", "the next rent is due");
+        assert!(journal.tasks[1].due > "2026-10-01".parse().ok());
+        assert_eq!(journal.tasks[2].text, "new, done");
+        assert!(journal.tasks[2].is_completed());
+        journal.check().unwrap();
+
+        // STATUS wins over a COMPLETED date that disagrees with it.
+        let open = parse(&todo("open", "STATUS:NEEDS-ACTION\r\nCOMPLETED:20261002T100000Z")).unwrap();
+        assert!(!open[0].is_completed() && open[0].completed_at.is_none());
+    }
+
+    #[test]
+    fn completions_with_open_subtasks_are_skipped() {
+        let mut journal = Journal::default();
+        journal.push(Task::new("move house".into()));
+        let mut boxes = Task::new("pack boxes".into());
+        boxes.parent = Some(1);
+        journal.push(boxes);
+
+        let mut exported = parse(&format(&journal.tasks[..1], Utc::now())).unwrap();
+        exported[0].text = "move house soon".into();
+        exported[0].complete();
+        let (ids, added, updated) = merge(&mut journal, exported).unwrap();
+        assert_eq!((ids, added, updated), (vec![1], 0, 1));
+        assert_eq!(journal.tasks[0].text, "move house soon");
+        assert!(!journal.tasks[0].is_completed());
+        journal.check().unwrap();
+    }
+}
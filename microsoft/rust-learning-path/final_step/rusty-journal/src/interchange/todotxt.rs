@@ -0,0 +1,295 @@
+//! The todo.txt format (<https://github.com/todotxt/todo.txt>), one task per line:
+//!
+//! ```text
+//! (A) 2026-10-01 Call the bank +finance @phone due:2026-10-20
+//! x 2026-10-18 2026-10-02 Renew passport @errands pri:B
+//! ```
+//!
+//! Priorities `(A)` and `(B)` are high and medium, and `(C)` to `(Z)` are low.
+//! A task's project is its last `+project`, a name starting with a letter;
+//! any others, and words such as `+1` or `+5%`, stay in the text. Every
+//! `@context` becomes a tag. The `due:`, `rec:`, `rrule:` and `pri:`
+//! extensions map to fields, and other `key:value` pairs are kept in
+//! `Task::extensions`.
+//!
+//! Export writes every task in the same layout as above, so tokens may move
+//! around, but the text, priority letters, projects, contexts and extensions
+//! come back the same. Dates do too, except that the journal needs a creation
+//! date: a line without one gets the date it was imported on, and is exported
+//! with it.
+
+use super::{local_date, local_midnight};
+use crate::recurrence::{Frequency, Recurrence};
+use crate::tasks::{parse_project, Priority, Status, Task};
+use chrono::{NaiveDate, Utc};
+
+/// Parse a todo.txt file. Blank lines are skipped.
+pub fn parse(text: &str) -> Result<Vec<Task>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| parse_line(line).map_err(|message| format!("line {}: {}", number + 1, message)))
+        .collect()
+}
+
+/// Write tasks as a todo.txt file.
+pub fn format(tasks: &[Task]) -> String {
+    tasks.iter().map(|task| format_task(task) + "\n").collect()
+}
+
+fn parse_line(line: &str) -> Result<Task, String> {
+    let mut words = line.split_whitespace().peekable();
+    let mut task = Task::new(String::new());
+
+    let completed = words.next_if_eq(&"x").is_some();
+    let mut letter = words.next_if(|word| priority_letter(word).is_some()).and_then(priority_letter);
+    // A completed task has its completion date first, then its creation date.
+    let first_date = words.next_if(|word| parse_date(word).is_some()).and_then(parse_date);
+    let second_date = if completed {
+        words.next_if(|word| parse_date(word).is_some()).and_then(parse_date)
+    } else {
+        None
+    };
+    let (completed_on, created_on) = if completed {
+        (first_date, second_date)
+    } else {
+        (None, first_date)
+    };
+
+    if completed {
+        task.status = Status::Completed;
+        task.completed_at = Some(completed_on.map_or_else(Utc::now, local_midnight));
+    }
+    if let Some(created_on) = created_on {
+        task.created_at = local_midnight(created_on);
+    }
+
+    let mut text: Vec<&str> = Vec::new();
+    for word in words {
+        if let Some(tag) = word.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            // A repeated context stays in the text so it isn't lost.
+            if task.tags.insert(tag.to_string()) {
+                continue;
+            }
+        } else if let Some((key, value)) = extension(word) {
+            let consumed = match key {
+                "due" if task.due.is_none() => parse_date(value).map(|due| task.due = Some(due)).is_some(),
+                "rec" if task.recurrence.is_none() => match parse_rec(value) {
+                    Some(recurrence) => {
+                        // Keep spellings such as `+1w` that export wouldn't reproduce.
+                        if format_rec(&recurrence).as_deref() != Some(value) {
+                            task.extensions.insert(key.to_string(), value.to_string());
+                        }
+                        task.recurrence = Some(recurrence);
+                        true
+                    }
+                    None => false,
+                },
+                "rrule" if task.recurrence.is_none() => {
+                    value.parse().map(|recurrence| task.recurrence = Some(recurrence)).is_ok()
+                }
+                "pri" if letter.is_none() && priority_letter(&format!("({})", value)).is_some() => {
+                    letter = value.chars().next();
+                    true
+                }
+                _ => false,
+            };
+            if consumed {
+                continue;
+            }
+            if !task.extensions.contains_key(key) {
+                task.extensions.insert(key.to_string(), value.to_string());
+                continue;
+            }
+        }
+        text.push(word);
+    }
+
+    // The last project is the task's; earlier ones stay in the text.
+    if let Some(index) = text.iter().rposition(|word| is_project(word)) {
+        task.project = Some(text.remove(index)[1..].to_string());
+    }
+
+    if let Some(letter) = letter {
+        let priority = priority_of(letter);
+        if letter_of(priority) != letter && !task.extensions.contains_key("pri") {
+            task.extensions.insert("pri".to_string(), letter.to_string());
+        }
+        task.priority = Some(priority);
+    }
+
+    task.text = text.join(" ");
+    if task.text.is_empty() {
+        return Err("the task has no text".to_string());
+    }
+    Ok(task)
+}
+
+fn format_task(task: &Task) -> String {
+    let mut words: Vec<String> = Vec::new();
+
+    // Prefer the letter the task was imported with, if it still fits.
+    let letter = task.priority.map(|priority| {
+        task.extensions
+            .get("pri")
+            .and_then(|pri| pri.chars().next())
+            .filter(|&letter| priority_of(letter) == priority)
+            .unwrap_or_else(|| letter_of(priority))
+    });
+
+    if task.is_completed() {
+        words.push("x".to_string());
+        if let Some(completed_at) = task.completed_at {
+            words.push(local_date(completed_at).to_string());
+        }
+    } else if let Some(letter) = letter {
+        words.push(format!("({})", letter));
+    }
+    words.push(local_date(task.created_at).to_string());
+    words.extend(task.text.split_whitespace().map(str::to_string));
+
+    if let Some(project) = &task.project {
+        words.push(format!("+{}", project));
+    }
+    words.extend(task.tags.iter().map(|tag| format!("@{}", tag)));
+    if let Some(due) = task.due {
+        words.push(format!("due:{}", due));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        let imported = task.extensions.get("rec").filter(|rec| parse_rec(rec).as_ref() == Some(recurrence));
+        match imported.cloned().or_else(|| format_rec(recurrence)) {
+            Some(rec) => words.push(format!("rec:{}", rec)),
+            None => words.push(format!("rrule:{}", recurrence.to_rrule())),
+        }
+    }
+    if let (true, Some(letter)) = (task.is_completed(), letter) {
+        words.push(format!("pri:{}", letter));
+    }
+    for (key, value) in &task.extensions {
+        let mapped = match key.as_str() {
+            "pri" => task.priority.is_some(),
+            "rec" => task.recurrence.is_some(),
+            _ => false,
+        };
+        if !mapped {
+            words.push(format!("{}:{}", key, value));
+        }
+    }
+
+    words.join(" ")
+}
+
+/// A `+project` word. The name must start with a letter, so `+1` is text.
+fn is_project(word: &str) -> bool {
+    word.strip_prefix('+')
+        .is_some_and(|name| name.starts_with(char::is_alphabetic) && parse_project(name).is_ok())
+}
+
+/// The letter in a priority such as `(A)`.
+fn priority_letter(word: &str) -> Option<char> {
+    match word.as_bytes() {
+        [b'(', letter @ b'A'..=b'Z', b')'] => Some(*letter as char),
+        _ => None,
+    }
+}
+
+fn priority_of(letter: char) -> Priority {
+    match letter {
+        'A' => Priority::High,
+        'B' => Priority::Medium,
+        _ => Priority::Low,
+    }
+}
+
+fn letter_of(priority: Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+/// A `key:value` word. URLs such as `https://example.com` are not extensions.
+fn extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    (valid_key && !value.is_empty() && !value.starts_with("//")).then_some((key, value))
+}
+
+/// The `rec:` extension used by todo.txt apps: `3d`, `+1w`, `2m`, `1y`. A
+/// leading `+` means counting from the due date, which is how the journal
+/// always counts.
+fn parse_rec(value: &str) -> Option<Recurrence> {
+    let value = value.strip_prefix('+').unwrap_or(value);
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = value.split_at(split);
+    let number = if number.is_empty() { "1" } else { number };
+    if !matches!(unit, "d" | "w" | "m" | "y") {
+        return None;
+    }
+    format!("every:{}{}", number, unit).parse().ok()
+}
+
+/// The `rec:` form of a recurrence, if it is simple enough to have one.
+fn format_rec(recurrence: &Recurrence) -> Option<String> {
+    if !recurrence.weekdays.is_empty()
+        || recurrence.month_day.is_some()
+        || recurrence.until.is_some()
+        || recurrence.count.is_some()
+    {
+        return None;
+    }
+    let unit = match recurrence.frequency {
+        Frequency::Daily => 'd',
+        Frequency::Weekly => 'w',
+        Frequency::Monthly => 'm',
+        Frequency::Yearly => 'y',
+    };
+    Some(format!("{}{}", recurrence.interval, unit))
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_lines_with_every_field() {
+        let file = "\
+(A) 2026-10-01 Call the bank +finance @phone due:2026-10-20
+x 2026-10-18 2026-10-02 Renew passport @errands pri:B
+(D) 2026-10-03 Water the plants +garden @home rec:+3d
+2026-10-04 Read https://example.com/a at 10:30 +extra-project +reading @couch @home estimate:2h id:7
+x 2026-10-05 2026-10-01 Pay rent rrule:FREQ=MONTHLY;BYMONTHDAY=1 pri:C
+";
+        let tasks = parse(file).unwrap();
+        assert_eq!(format(&tasks), file);
+
+        assert_eq!(tasks[0].priority, Some(Priority::High));
+        assert_eq!(tasks[0].project.as_deref(), Some("finance"));
+        assert_eq!(tasks[0].due, "2026-10-20".parse().ok());
+        assert!(tasks[1].is_completed());
+        assert_eq!(tasks[1].priority, Some(Priority::Medium));
+        assert_eq!(tasks[2].priority, Some(Priority::Low));
+        assert_eq!(tasks[2].recurrence, "every:3d".parse().ok());
+        assert_eq!(tasks[3].text, "Read https://example.com/a at 10:30 +extra-project");
+        assert_eq!(tasks[3].extensions.get("estimate").map(String::as_str), Some("2h"));
+    }
+
+    #[test]
+    fn loose_lines_are_normalised() {
+        let tasks = parse("\n(B) Call +home Mom @phone soon +family +1 +5% due:2026-13-01\n").unwrap();
+        assert_eq!(tasks.len(), 1);
+        let task = &tasks[0];
+        assert_eq!(task.text, "Call +home Mom soon +1 +5%");
+        assert_eq!(task.project.as_deref(), Some("family"));
+        assert_eq!(task.due, None);
+        assert_eq!(task.extensions.get("due").map(String::as_str), Some("2026-13-01"));
+
+        assert!(parse("ok\n(A) +only @tokens").unwrap_err().starts_with("line 2:"));
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to sleep between attempts to take the lock.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An exclusive advisory lock on a journal, held until dropped.
+///
+/// The lock is taken on a `<journal>.lock` file next to the journal rather than
+/// the journal itself, because saving replaces the journal file with a new one.
+#[derive(Debug)]
+pub struct JournalLock {
+    _file: File,
+}
+
+impl JournalLock {
+    /// Take the lock, waiting up to `timeout` for another process to let go of it.
+    pub fn acquire(journal_path: &Path, timeout: Duration) -> Result<JournalLock> {
+        let lock_path = lock_path(journal_path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)?;
+
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(JournalLock { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                    thread::sleep(RETRY_INTERVAL)
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Error::new(
+                        ErrorKind::WouldBlock,
+                        format!(
+                            "The journal is in use by another rusty-journal process \
+                             (gave up after {:.1}s waiting for {})",
+                            timeout.as_secs_f64(),
+                            lock_path.display()
+                        ),
+                    ))
+                }
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+fn lock_path(journal_path: &Path) -> PathBuf {
+    let mut name = journal_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    journal_path.with_file_name(name)
+}
@@ -1,11 +1,42 @@
 use structopt::StructOpt;
 use anyhow::anyhow;
+mod agenda;
 mod cli;
+mod config;
+mod dates;
+mod editor;
+mod graph;
+mod history;
+mod interchange;
+mod lock;
+mod migrations;
+mod output;
+mod projects;
+mod quickadd;
+mod query;
+mod recurrence;
+mod report;
+mod search;
+mod storage;
+mod table;
 mod tasks;
+mod timesheet;
+mod tui;
 
-use cli::{Action::*, CommandLineArgs};
-use tasks::Task;
+use cli::{Action::*, CommandLineArgs, ConfigAction, TagAction};
+use config::{Config, Source};
+use history::Recorder;
+use lock::JournalLock;
+use output::{Columns, OutputFormat};
+use quickadd::QuickAdd;
+use query::Query;
+use storage::Backend;
+use tasks::{ListOptions, Modification, Place, Show};
+use std::env;
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
 
 fn find_default_journal_file() -> Option<PathBuf> {
@@ -15,23 +46,356 @@ fn find_default_journal_file() -> Option<PathBuf> {
     })
 }
 
-fn main() -> anyhow::Result<()> {
-    // Get the command-line arguments.
+/// Picking fields only makes sense for tabular output.
+fn tabular(format: OutputFormat, fields: &Option<Columns>) -> OutputFormat {
+    match (format, fields) {
+        (OutputFormat::Text, Some(_)) => OutputFormat::Table,
+        _ => format,
+    }
+}
+
+/// The output format asked for on the command line, read straight from the
+/// arguments for when they couldn't be parsed. Anything after `--` is task
+/// text, and so is a word such as `-fix` that only looks like `-f<format>`.
+fn requested_format(args: &[OsString]) -> OutputFormat {
+    let args: Vec<&str> = args
+        .iter()
+        .map(|arg| arg.to_str().unwrap_or_default())
+        .take_while(|arg| *arg != "--")
+        .collect();
+    args.iter()
+        .enumerate()
+        .find_map(|(index, arg)| match *arg {
+            "-f" | "--format" => args.get(index + 1).and_then(|format| format.parse().ok()),
+            _ => arg
+                .strip_prefix("--format=")
+                .and_then(|format| format.parse().ok())
+                .or_else(|| arg.strip_prefix("-f").and_then(|format| format.parse().ok())),
+        })
+        .unwrap_or_default()
+}
+
+/// Report an error the way `format` asks for, and exit.
+fn fail(format: OutputFormat, error: anyhow::Error) -> ! {
+    if format.is_machine() {
+        eprintln!("{}", output::error_json(&error));
+    } else {
+        eprintln!("Error: {:?}", error);
+    }
+    process::exit(1);
+}
+
+fn main() {
+    // Get the command-line arguments, with aliases from the config expanded.
+    // With a machine format, errors are printed as JSON so scripts don't have
+    // to scrape them.
+    let raw_args: Vec<OsString> = env::args_os().collect();
+    let config = Config::load().unwrap_or_else(|error| fail(requested_format(&raw_args), error.into()));
+    let args = CommandLineArgs::from_iter_safe(&raw_args).or_else(|error| {
+        // Subcommands win over aliases of the same name.
+        use structopt::clap::ErrorKind::{InvalidSubcommand, UnknownArgument, UnrecognizedSubcommand};
+        match config.expand_alias(&raw_args) {
+            Some(expanded) if matches!(error.kind, InvalidSubcommand | UnknownArgument | UnrecognizedSubcommand) => {
+                CommandLineArgs::from_iter_safe(expanded)
+            }
+            _ => Err(error),
+        }
+    });
+    let args = match args {
+        Ok(args) => args,
+        Err(error) if !error.use_stderr() || !requested_format(&raw_args).is_machine() => error.exit(),
+        Err(error) => {
+            let message = error.message.lines().next().unwrap_or_default();
+            let message = message.trim_start_matches("error: ");
+            eprintln!("{}", serde_json::json!({ "error": { "kind": "usage", "message": message } }));
+            process::exit(1);
+        }
+    };
+
+    let format = args.action.output_format();
+    if let Err(error) = run(args, &config) {
+        fail(format, error);
+    }
+}
+
+fn run(args: CommandLineArgs, config: &Config) -> anyhow::Result<()> {
     let CommandLineArgs {
         action,
         journal_file,
-    } = CommandLineArgs::from_args();
+        backend,
+        history_depth,
+        lock_timeout,
+    } = args;
+
+    // Settings don't need the journal.
+    match &action {
+        Config(ConfigAction::Get { key }) => return Ok(config::get(config, key)?),
+        Config(ConfigAction::Set { key, value }) => return Ok(config::set(config, key, value)?),
+        Config(ConfigAction::List) => return Ok(config::list(config)?),
+        _ => {}
+    }
 
-    // Unpack the journal file.
-    let journal_file = journal_file
+    // Flags win over the config, whose values were checked when it was loaded.
+    config::set_appearance(config.appearance());
+    let backend = backend.or_else(|| config.parse("backend"));
+    let history_depth = history_depth.or_else(|| config.parse("history_depth")).unwrap_or(100);
+    let lock_timeout = lock_timeout.or_else(|| config.parse("lock_timeout")).unwrap_or(10.0);
+
+    // The filters are needed before opening the journal, for `list --everywhere`.
+    let list_options = match &action {
+        List {
+            query,
+            all,
+            completed,
+            sort,
+            tags,
+            project,
+            ready,
+            blocked,
+            ..
+        } => Some(ListOptions {
+            show: match (all, completed) {
+                (true, _) => Show::All,
+                (_, true) => Show::Completed,
+                _ => Show::Open,
+            },
+            sort: sort.or_else(|| config.parse("sort")),
+            tags: tags.clone(),
+            project: project.clone(),
+            query: query.as_deref().map(Query::parse).transpose()?,
+            blocked: match (ready, blocked) {
+                (true, _) => Some(false),
+                (_, true) => Some(true),
+                _ => None,
+            },
+        }),
+        _ => None,
+    };
+
+    // Unpack the journal file: the flag or environment variable, then the
+    // nearest project journal, then the `journal` setting.
+    let global_journal = config
+        .journal()
         .or_else(find_default_journal_file)
         .ok_or(anyhow!("Failed to find journal file."))?;
+    let pinned = journal_file.or_else(|| match config.get("journal") {
+        Some((_, Source::Environment)) => config.journal(),
+        _ => None,
+    });
+    let use_global = matches!(action, List { global: true, .. } | List { everywhere: true, .. });
+    // Walking up from somewhere in the home directory finds the global journal
+    // too, which isn't a project's.
+    let project_journal = match &pinned {
+        None if !use_global => projects::find_project_journal(&env::current_dir()?)
+            .filter(|path| !projects::same_file(path, &global_journal)),
+        _ => None,
+    };
+    let lock_timeout = Duration::from_secs_f64(lock_timeout);
+    if let Some(path) = &project_journal {
+        if let Err(error) = projects::register(path, lock_timeout) {
+            eprintln!("Warning: couldn't remember {} for list --everywhere: {}", path.display(), error);
+        }
+    }
+    let journal_file = pinned.or(project_journal).unwrap_or(global_journal);
+
+    // These work on several journals, or on one that doesn't exist yet.
+    match (&action, &list_options) {
+        (Init { dir }, _) => {
+            let dir = match dir {
+                Some(dir) => dir.clone(),
+                None => env::current_dir()?,
+            };
+            return Ok(projects::init(&dir, lock_timeout)?);
+        }
+        (
+            List {
+                everywhere: true,
+                format,
+                fields,
+                ..
+            },
+            Some(options),
+        ) => {
+            let format = tabular(*format, fields);
+            let columns = fields.clone().unwrap_or_default();
+            return Ok(projects::list_everywhere(
+                &journal_file,
+                backend,
+                lock_timeout,
+                options,
+                format,
+                &columns,
+            )?);
+        }
+        // The interface stays open a long time, so it only takes the lock
+        // while it loads or changes the journal, and records each change itself.
+        (Tui, _) => {
+            let mut storage = {
+                let _lock = JournalLock::acquire(&journal_file, lock_timeout)?;
+                storage::open(&journal_file, backend)?
+            };
+            return Ok(tui::run(storage.as_mut(), &journal_file, lock_timeout, history_depth)?);
+        }
+        _ => {}
+    }
+
+    // Hold the journal lock for the whole command, so concurrent runs queue up
+    // instead of overwriting each other's changes.
+    let _lock = JournalLock::acquire(&journal_file, lock_timeout)?;
+    let mut storage = storage::open(&journal_file, backend)?;
+
+    // Undo and redo work on the history themselves; everything else gets
+    // recorded so it can be undone.
+    match action {
+        Undo { list: true, .. } => return Ok(history::list_history(storage.as_mut())?),
+        Undo { count, .. } => return Ok(history::undo(storage.as_mut(), count)?),
+        Redo { count } => return Ok(history::redo(storage.as_mut(), count)?),
+        _ => {}
+    }
+    let mut recorder = Recorder::new(storage.as_mut(), action.name(), history_depth);
+    let storage = &mut recorder;
 
     // Perform the action.
     match action {
-        Add { task } => tasks::add_task(journal_file, Task::new(task)),
-        List => tasks::list_tasks(journal_file),
-        Done { position } => tasks::complete_task(journal_file, position),
+        Add {
+            task,
+            due,
+            priority,
+            tags,
+            project,
+            recur,
+            parent,
+            dry_run,
+        } => {
+            // Options win over fields written in the text.
+            let today = chrono::Local::now().date_naive();
+            let mut parsed = QuickAdd::parse(&task, today).map_err(|error| anyhow!(error))?;
+            parsed.due = due.or(parsed.due);
+            parsed.priority = priority.or(parsed.priority);
+            parsed.tags.extend(tags);
+            parsed.project = project.or(parsed.project);
+            parsed.recurrence = recur.or(parsed.recurrence);
+            let mut task = parsed.into_task(today);
+            task.parent = parent;
+            if dry_run {
+                quickadd::print_parsed(&task);
+                return Ok(());
+            }
+            tasks::add_task(storage, task)
+        }
+        List { format, fields, .. } => {
+            let options = list_options.expect("built for list");
+            tasks::list_tasks(storage, options, tabular(format, &fields), &fields.unwrap_or_default())
+        }
+        Done { id, recursive } => tasks::complete_task(storage, id, recursive),
+        Purge { ids } => tasks::purge_tasks(storage, &ids),
+        Tags => tasks::list_tags(storage),
+        Tag(TagAction::Add { id, tags }) => tasks::tag_task(storage, id, &tags, false),
+        Tag(TagAction::Remove { id, tags }) => tasks::tag_task(storage, id, &tags, true),
+        Modify {
+            id,
+            text,
+            due,
+            priority,
+            tags,
+            untag,
+            project,
+            recur,
+            parent,
+            clear,
+        } => {
+            let modification = Modification {
+                text,
+                due,
+                priority,
+                project,
+                recurrence: recur,
+                parent,
+                add_tags: tags,
+                remove_tags: untag,
+                clear,
+            };
+            tasks::modify_task(storage, id, &modification)
+        }
+        Edit { id } => tasks::edit_tasks(storage, id),
+        Move {
+            id,
+            before,
+            after,
+            top,
+            bottom,
+        } => {
+            let place = match (before, after) {
+                (Some(other), _) => Place::Before(other),
+                (_, Some(other)) => Place::After(other),
+                _ if top => Place::Top,
+                _ if bottom => Place::Bottom,
+                _ => return Err(anyhow!("Say where to move the task: --before, --after, --top or --bottom.")),
+            };
+            tasks::move_task(storage, id, place)
+        }
+        Depends { id, depends, remove } => tasks::depend_task(storage, id, &depends, remove),
+        Search {
+            terms,
+            limit,
+            format,
+            fields,
+        } => {
+            search::search_tasks(storage, &terms, limit, tabular(format, &fields), &fields.unwrap_or_default())
+        }
+        Agenda { format, fields } => agenda::print_agenda(storage, format, &fields.unwrap_or_default()),
+        Calendar { month, format } => agenda::print_calendar(storage, month, format),
+        Start { id } => timesheet::start_task(storage, id),
+        Stop => timesheet::stop_task(storage),
+        Log { id, spent, date, at } => timesheet::log_time(storage, id, spent, date, at),
+        Timesheet {
+            week,
+            date,
+            round,
+            rounding,
+            format,
+        } => {
+            let date = date.unwrap_or_else(|| chrono::Local::now().date_naive());
+            let (from, to) = if week { timesheet::week_of(date) } else { (date, date) };
+            timesheet::print_timesheet(storage, from, to, round.map(|step| (step, rounding)), format)
+        }
+        Report { by, periods, format } => report::print_report(storage, by, periods, format),
+        Graph { all } => graph::print_graph(storage, all),
+        Import { format, file } => interchange::import(storage, format, &file),
+        Export { format, output } => interchange::export(storage, format, output.as_deref()),
+        Migrate { to, output } => {
+            let extension = match to {
+                Backend::Json => "json",
+                Backend::Sqlite => "db",
+            };
+            let output = output.unwrap_or_else(|| journal_file.with_extension(extension));
+            if output == journal_file {
+                return Err(anyhow!("The copy would overwrite the journal; pick another --output."));
+            }
+            let _output_lock = JournalLock::acquire(&output, lock_timeout)?;
+            let mut target = storage::open(&output, Some(to))?;
+            tasks::migrate_journal(storage, target.as_mut())?;
+            println!("Wrote {}.", output.display());
+            Ok(())
+        }
+        Undo { .. } | Redo { .. } | Tui | Config(_) | Init { .. } => unreachable!("handled before recording starts"),
     }?;
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_of(args: &[&str]) -> OutputFormat {
+        requested_format(&args.iter().map(OsString::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn finds_the_format_only_in_options() {
+        assert_eq!(format_of(&["rj", "list", "-f", "json"]), OutputFormat::Json);
+        assert_eq!(format_of(&["rj", "list", "--format=json"]), OutputFormat::Json);
+        assert_eq!(format_of(&["rj", "list", "-fjson"]), OutputFormat::Json);
+        assert_eq!(format_of(&["rj", "add", "-fix", "-f", "json"]), OutputFormat::Json);
+        assert_eq!(format_of(&["rj", "add", "--", "-f", "json"]), OutputFormat::Text);
+    }
+}
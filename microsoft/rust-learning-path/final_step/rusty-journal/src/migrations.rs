@@ -0,0 +1,174 @@
+use serde_json::{json, Map, Value};
+use std::io::{Error, ErrorKind, Result};
+
+/// The journal format this binary reads and writes.
+pub const CURRENT_VERSION: u32 = 3;
+
+/// One step that upgrades a journal from `from` to `from + 1`.
+struct Migration {
+    from: u32,
+    apply: fn(Value) -> Result<Value>,
+}
+
+/// Every layout the journal has ever had, oldest first:
+///
+/// 1. A bare array of `{text, created_at}` tasks.
+/// 2. `{"next_id": N, "tasks": [...]}`, once tasks got IDs.
+/// 3. `{"version": 3, "next_id": N, "tasks": [...]}`, the versioned envelope.
+///
+/// A layout change means bumping `CURRENT_VERSION` and adding a step here.
+const MIGRATIONS: &[Migration] = &[
+    Migration { from: 1, apply: add_ids },
+    Migration { from: 2, apply: add_version },
+];
+
+/// Work out which layout a parsed journal file uses.
+pub fn detect_version(value: &Value) -> Result<u32> {
+    match value {
+        Value::Array(_) => Ok(1),
+        Value::Object(object) => match object.get("version") {
+            None => Ok(2),
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| invalid(format!("Invalid journal version {}", version))),
+        },
+        _ => Err(invalid("The journal file is neither a task list nor a journal object".to_string())),
+    }
+}
+
+/// Bring a journal up to `CURRENT_VERSION`, one step at a time, and give
+/// tasks without an ID one.
+///
+/// Journals newer than this binary knows about are returned untouched.
+pub fn upgrade(mut value: Value) -> Result<Value> {
+    let mut version = detect_version(&value)?;
+    if version > CURRENT_VERSION {
+        return Ok(value);
+    }
+    while version < CURRENT_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| invalid(format!("Don't know how to upgrade a version {} journal", version)))?;
+        value = (migration.apply)(value)?;
+        version += 1;
+    }
+    number_missing_ids(&mut value)?;
+    Ok(value)
+}
+
+/// 1 -> 2: wrap the bare array and number the tasks in file order.
+fn add_ids(value: Value) -> Result<Value> {
+    let Value::Array(mut tasks) = value else {
+        return Err(invalid("A version 1 journal must be an array".to_string()));
+    };
+
+    let mut next_id = 1;
+    for task in tasks.iter_mut() {
+        let task = task
+            .as_object_mut()
+            .ok_or_else(|| invalid("Every task in the journal must be an object".to_string()))?;
+        task.insert("id".to_string(), json!(next_id));
+        next_id += 1;
+    }
+    Ok(json!({ "next_id": next_id, "tasks": tasks }))
+}
+
+/// 2 -> 3: add the version marker.
+fn add_version(value: Value) -> Result<Value> {
+    let Value::Object(object) = value else {
+        return Err(invalid("A version 2 journal must be an object".to_string()));
+    };
+
+    // Rebuild the object so the version comes first in the file.
+    let mut upgraded = Map::new();
+    upgraded.insert("version".to_string(), json!(3));
+    upgraded.extend(object);
+    Ok(Value::Object(upgraded))
+}
+
+/// Number tasks without an ID, such as ones added to the file by hand or by an
+/// older rusty-journal, after the highest ID handed out, and make sure
+/// `next_id` is past every ID in use. Unlike the steps
+/// above this runs on every load, since a journal of any version can be edited.
+fn number_missing_ids(value: &mut Value) -> Result<()> {
+    let next_id = value.get("next_id").and_then(Value::as_u64).unwrap_or(1);
+    let Some(Value::Array(tasks)) = value.get_mut("tasks") else {
+        return Err(invalid("The journal has no task list".to_string()));
+    };
+    let highest = tasks.iter().filter_map(|task| task.get("id")?.as_u64()).max().unwrap_or(0);
+    let mut next_id = next_id.max(highest + 1);
+    for (index, task) in tasks.iter_mut().enumerate() {
+        let task = task
+            .as_object_mut()
+            .ok_or_else(|| invalid(format!("Task #{} in the journal isn't an object", index + 1)))?;
+        if task.get("id").is_none_or(Value::is_null) {
+            task.insert("id".to_string(), json!(next_id));
+            next_id += 1;
+        }
+    }
+    // Also raises a `next_id` edited below an ID in use, so it's never handed out again.
+    value["next_id"] = json!(next_id);
+    Ok(())
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_the_original_bare_array() {
+        let legacy: Value = serde_json::from_str(include_str!("../test-journal.json")).unwrap();
+        assert_eq!(detect_version(&legacy).unwrap(), 1);
+
+        let upgraded = upgrade(legacy).unwrap();
+        assert_eq!(detect_version(&upgraded).unwrap(), CURRENT_VERSION);
+        assert_eq!(upgraded["next_id"], 3);
+        assert_eq!(upgraded["tasks"][0]["id"], 1);
+        assert_eq!(upgraded["tasks"][1]["text"], "water the plants");
+    }
+
+    #[test]
+    fn leaves_newer_journals_alone() {
+        let newer = json!({ "version": CURRENT_VERSION + 1, "next_id": 1, "tasks": [] });
+        assert_eq!(upgrade(newer.clone()).unwrap(), newer);
+    }
+
+    #[test]
+    fn numbers_tasks_without_an_id() {
+        let edited = json!({
+            "version": CURRENT_VERSION,
+            "next_id": 3,
+            "tasks": [
+                { "id": 1, "text": "one", "created_at": 0 },
+                { "text": "added by hand", "created_at": 0 },
+                { "id": 4, "text": "beyond next_id", "created_at": 0 },
+            ],
+        });
+        let upgraded = upgrade(edited).unwrap();
+        assert_eq!(upgraded["tasks"][1]["id"], 5);
+        assert_eq!(upgraded["next_id"], 6);
+        let journal: crate::tasks::Journal = serde_json::from_value(upgraded).unwrap();
+        assert_eq!(journal.tasks[1].text, "added by hand");
+    }
+
+    #[test]
+    fn raises_a_stale_next_id() {
+        let edited = json!({
+            "version": CURRENT_VERSION,
+            "next_id": 2,
+            "tasks": [
+                { "id": 1, "text": "one", "created_at": 0 },
+                { "id": 2, "text": "two", "created_at": 0 },
+            ],
+        });
+        let mut journal: crate::tasks::Journal = serde_json::from_value(upgrade(edited).unwrap()).unwrap();
+        assert_eq!(journal.push(crate::tasks::Task::new("three".to_string())), 3);
+        journal.check().unwrap();
+    }
+}
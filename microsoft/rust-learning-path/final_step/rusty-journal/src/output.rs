@@ -0,0 +1,494 @@
+//! Output formats for commands that print tasks, for people and for scripts.
+//!
+//! # JSON schema
+//!
+//! `--format json` prints an array of task objects and `--format jsonl` prints
+//! one task object per line. Every object has exactly the keys picked with
+//! `--fields`, in that order, or all of the keys below by default. Unset values
+//! are `null` rather than missing.
+//!
+//! | Key          | Type                | Notes                                        |
+//! |--------------|---------------------|----------------------------------------------|
+//! | `id`         | integer             | The ID other commands take.                  |
+//! | `uid`        | string              | Stable across journals, as used in `.ics`.   |
+//! | `parent`     | integer or null     | The task this is a subtask of.               |
+//! | `text`       | string              |                                              |
+//! | `status`     | string              | `pending` or `completed`.                    |
+//! | `created`    | string              | RFC 3339 in UTC, e.g. `2026-10-01T09:30:00Z`. |
+//! | `completed`  | string or null      | RFC 3339 in UTC.                             |
+//! | `due`        | string or null      | `YYYY-MM-DD`.                                |
+//! | `priority`   | string or null      | `high`, `medium` or `low`.                   |
+//! | `project`    | string or null      |                                              |
+//! | `tags`       | array of strings    | Sorted.                                      |
+//! | `depends`    | array of integers   | The tasks this one waits for, sorted.        |
+//! | `blocked`    | boolean             | Whether any of `depends` is still open.      |
+//! | `overdue`    | boolean             |                                              |
+//! | `recurrence` | string or null      | An iCalendar RRULE, e.g. `FREQ=WEEKLY;BYDAY=MO`. |
+//! | `urgency`    | number              | Rounded to two decimals.                     |
+//! | `source`     | string or null      | The journal, with `list --everywhere`.       |
+//!
+//! Keys are only ever added to this schema, never renamed or removed.
+//!
+//! CSV and TSV have a header row of the same names. Lists are joined with
+//! commas, and `null` is an empty cell. CSV quotes cells as RFC 4180 says; TSV
+//! has no quoting, so tabs and line breaks in values become spaces.
+//!
+//! # Errors
+//!
+//! With a machine format, a failing command prints one JSON object to stderr
+//! instead of a message, and exits with status 1:
+//!
+//! ```json
+//! {"error": {"kind": "invalid_input", "message": "No task with ID 9"}}
+//! ```
+//!
+//! Invalid `list` queries also carry `query` and the 1-based `column` of the problem.
+
+use crate::config;
+use crate::query::QueryError;
+use crate::table::{Cell, Colour, Table, Terminal};
+use crate::tasks::{Journal, Priority, Task};
+use chrono::{Local, NaiveDate, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How a command prints its results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The human-readable output each command has always had.
+    #[default]
+    Text,
+    Table,
+    Json,
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    /// Whether the output is meant for other programs, in which case errors
+    /// are reported as JSON too.
+    pub fn is_machine(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::Csv | OutputFormat::Tsv)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("Unknown format '{}', expected text, table, json, jsonl, csv or tsv", s)),
+        }
+    }
+}
+
+/// A piece of task data that can be printed, see the schema above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    Uid,
+    Parent,
+    Text,
+    Status,
+    Created,
+    Completed,
+    Due,
+    Priority,
+    Project,
+    Tags,
+    Depends,
+    Blocked,
+    Overdue,
+    Recurrence,
+    Urgency,
+    Source,
+}
+
+impl Column {
+    pub const ALL: [Column; 17] = [
+        Column::Id,
+        Column::Uid,
+        Column::Parent,
+        Column::Text,
+        Column::Status,
+        Column::Created,
+        Column::Completed,
+        Column::Due,
+        Column::Priority,
+        Column::Project,
+        Column::Tags,
+        Column::Depends,
+        Column::Blocked,
+        Column::Overdue,
+        Column::Recurrence,
+        Column::Urgency,
+        Column::Source,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Uid => "uid",
+            Column::Parent => "parent",
+            Column::Text => "text",
+            Column::Status => "status",
+            Column::Created => "created",
+            Column::Completed => "completed",
+            Column::Due => "due",
+            Column::Priority => "priority",
+            Column::Project => "project",
+            Column::Tags => "tags",
+            Column::Depends => "depends",
+            Column::Blocked => "blocked",
+            Column::Overdue => "overdue",
+            Column::Recurrence => "recurrence",
+            Column::Urgency => "urgency",
+            Column::Source => "source",
+        }
+    }
+
+    fn value(self, entry: &Entry) -> Value {
+        let Entry { task, open, source } = *entry;
+        match self {
+            Column::Id => json!(task.id),
+            Column::Uid => json!(task.uid()),
+            Column::Parent => json!(task.parent),
+            Column::Text => json!(task.text),
+            Column::Status => json!(task.status),
+            Column::Created => json!(task.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+            Column::Completed => {
+                json!(task.completed_at.map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true)))
+            }
+            Column::Due => json!(task.due),
+            Column::Priority => json!(task.priority),
+            Column::Project => json!(task.project),
+            Column::Tags => json!(task.tags),
+            Column::Depends => json!(task.depends),
+            Column::Blocked => json!(task.is_blocked(open)),
+            Column::Overdue => json!(task.is_overdue(Local::now().date_naive())),
+            Column::Recurrence => json!(task.recurrence),
+            Column::Urgency => json!((task.urgency(Utc::now()) * 100.0).round() / 100.0),
+            Column::Source => json!(source),
+        }
+    }
+}
+
+/// The columns picked with `--fields`, e.g. `id,text,due`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns(pub Vec<Column>);
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns(Column::ALL.to_vec())
+    }
+}
+
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s
+            .split(',')
+            .map(|name| {
+                let name = name.trim().to_lowercase();
+                Column::ALL.into_iter().find(|column| column.name() == name).ok_or_else(|| {
+                    let names: Vec<&str> = Column::ALL.iter().map(|column| column.name()).collect();
+                    format!("Unknown field '{}', expected some of {}", name, names.join(", "))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Columns(columns))
+    }
+}
+
+/// A task to print, with what its columns need to know about its journal.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    pub task: &'a Task,
+    /// The IDs of the open tasks in the task's journal.
+    pub open: &'a HashSet<u64>,
+    /// Where the task came from, when tasks from several journals are shown.
+    pub source: Option<&'a str>,
+}
+
+/// Print tasks in one of the tabular formats. `Text` is each command's own
+/// business, so it is printed as a table here.
+pub fn print_tasks(journal: &Journal, tasks: &[&Task], format: OutputFormat, columns: &Columns) {
+    let open = journal.open_ids();
+    let entries: Vec<Entry> = tasks
+        .iter()
+        .map(|&task| Entry {
+            task,
+            open: &open,
+            source: None,
+        })
+        .collect();
+    print_entries(&entries, format, columns);
+}
+
+/// Print tasks that may come from different journals, see `print_tasks`.
+pub fn print_entries(entries: &[Entry], format: OutputFormat, columns: &Columns) {
+    print!("{}", render_entries(entries, format, columns, Terminal::stdout()));
+}
+
+/// Print tasks in groups, such as the sections of `agenda`, in one of the
+/// machine formats: JSON gets a `{"group", "tasks"}` object per group, and
+/// the others a `group` field first on every task.
+pub fn print_groups(groups: &[(&str, Vec<Entry>)], format: OutputFormat, columns: &Columns) {
+    print!("{}", render_groups(groups, format, columns));
+}
+
+fn render_groups(groups: &[(&str, Vec<Entry>)], format: OutputFormat, columns: &Columns) -> String {
+    let mut out = String::new();
+    match format {
+        OutputFormat::Json => {
+            let groups: Vec<Value> = groups
+                .iter()
+                .map(|(group, entries)| {
+                    let tasks: Vec<Value> = entries.iter().map(|entry| Value::Object(object(entry, columns))).collect();
+                    json!({ "group": group, "tasks": tasks })
+                })
+                .collect();
+            out = serde_json::to_string_pretty(&groups).unwrap_or_default();
+            out.push('\n');
+        }
+        OutputFormat::Jsonl => {
+            for (group, entries) in groups {
+                for entry in entries {
+                    let mut fields = Map::new();
+                    fields.insert("group".to_string(), json!(group));
+                    fields.extend(object(entry, columns));
+                    writeln!(out, "{}", Value::Object(fields)).unwrap();
+                }
+            }
+        }
+        // CSV or TSV; text is up to each command.
+        _ => {
+            let header: Vec<String> =
+                std::iter::once("group".to_string()).chain(columns.0.iter().map(|column| column.name().to_string())).collect();
+            out.push_str(&delimited_line(format, &header));
+            for (group, entries) in groups {
+                for entry in entries {
+                    let cells: Vec<String> =
+                        std::iter::once(group.to_string()).chain(row(entry, columns).iter().map(cell)).collect();
+                    out.push_str(&delimited_line(format, &cells));
+                }
+            }
+        }
+    }
+    out
+}
+
+fn row(entry: &Entry, columns: &Columns) -> Vec<Value> {
+    columns.0.iter().map(|column| column.value(entry)).collect()
+}
+
+fn object(entry: &Entry, columns: &Columns) -> Map<String, Value> {
+    columns
+        .0
+        .iter()
+        .zip(row(entry, columns))
+        .map(|(column, value)| (column.name().to_string(), value))
+        .collect()
+}
+
+fn render_entries(entries: &[Entry], format: OutputFormat, columns: &Columns, terminal: Terminal) -> String {
+    let row = |entry: &Entry| row(entry, columns);
+    let object = |entry: &Entry| Value::Object(object(entry, columns));
+    let header: Vec<String> = columns.0.iter().map(|column| column.name().to_string()).collect();
+
+    let mut out = String::new();
+    match format {
+        OutputFormat::Json => {
+            let tasks: Vec<Value> = entries.iter().map(object).collect();
+            out = serde_json::to_string_pretty(&tasks).unwrap_or_default();
+            out.push('\n');
+        }
+        OutputFormat::Jsonl => {
+            for entry in entries {
+                writeln!(out, "{}", object(entry)).unwrap();
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            out.push_str(&delimited_line(format, &header));
+            for entry in entries {
+                let cells: Vec<String> = row(entry).iter().map(cell).collect();
+                out.push_str(&delimited_line(format, &cells));
+            }
+        }
+        OutputFormat::Table | OutputFormat::Text => {
+            let today = Local::now().date_naive();
+            let mut table = Table::new(header);
+            if let Some(text) = columns.0.iter().position(|&column| column == Column::Text) {
+                table = table.flexible(text);
+            }
+            for entry in entries {
+                let task = entry.task;
+                let cells = columns
+                    .0
+                    .iter()
+                    .zip(row(entry))
+                    .map(|(&column, value)| {
+                        let colour = match column {
+                            Column::Priority => task.priority.and_then(priority_colour),
+                            Column::Due => due_colour(task, today),
+                            _ => None,
+                        };
+                        Cell::coloured(cell(&value), colour)
+                    })
+                    .collect();
+                table.push(cells, task.is_completed().then_some(Colour::Dim));
+            }
+            out = table.render(terminal);
+        }
+    }
+    out
+}
+
+/// One line of CSV or TSV output, with its line ending.
+pub fn delimited_line(format: OutputFormat, cells: &[String]) -> String {
+    if format == OutputFormat::Csv {
+        let cells: Vec<String> = cells.iter().map(|cell| csv_quote(cell)).collect();
+        cells.join(",") + "\r\n"
+    } else {
+        let cells: Vec<String> = cells.iter().map(|cell| cell.replace(['\t', '\n', '\r'], " ")).collect();
+        cells.join("\t") + "\n"
+    }
+}
+
+/// The colour priorities are shown in, from the `colours` settings.
+pub fn priority_colour(priority: Priority) -> Option<Colour> {
+    let appearance = config::appearance();
+    match priority {
+        Priority::High => appearance.high,
+        Priority::Medium => appearance.medium,
+        Priority::Low => appearance.low,
+    }
+}
+
+/// The colour for open tasks that are overdue or due today.
+pub fn due_colour(task: &Task, today: NaiveDate) -> Option<Colour> {
+    let appearance = config::appearance();
+    match task.due {
+        _ if task.is_completed() => None,
+        _ if task.is_overdue(today) => appearance.overdue,
+        Some(due) if due == today => appearance.today,
+        _ => None,
+    }
+}
+
+/// A JSON value as a plain table cell.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
+
+fn csv_quote(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// The error object printed on stderr when a machine format is selected.
+pub fn error_json(error: &anyhow::Error) -> Value {
+    let mut body = Map::new();
+    if let Some(query) = error.downcast_ref::<QueryError>() {
+        body.insert("kind".into(), json!("invalid_query"));
+        body.insert("message".into(), json!(query.message));
+        body.insert("query".into(), json!(query.query));
+        body.insert("column".into(), json!(query.column));
+    } else {
+        let kind = match error.downcast_ref::<std::io::Error>() {
+            Some(error) => snake_case(&format!("{:?}", error.kind())),
+            None => "error".to_string(),
+        };
+        body.insert("kind".into(), json!(kind));
+        body.insert("message".into(), json!(error.to_string()));
+    }
+    json!({ "error": body })
+}
+
+/// `InvalidInput` to `invalid_input`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal() -> Journal {
+        let mut journal = Journal::default();
+        journal.push(Task::new("plain".into()));
+        let mut quoted = Task::new("say \"hi\", then\tleave".into());
+        quoted.tags.extend(["a".to_string(), "b".to_string()]);
+        quoted.due = "2026-10-20".parse().ok();
+        journal.push(quoted);
+        journal
+    }
+
+    fn render(journal: &Journal, format: OutputFormat, columns: &Columns) -> String {
+        let open = journal.open_ids();
+        let entries: Vec<Entry> = journal
+            .tasks
+            .iter()
+            .map(|task| Entry {
+                task,
+                open: &open,
+                source: (task.id == 2).then_some("~/work"),
+            })
+            .collect();
+        render_entries(&entries, format, columns, Terminal::plain())
+    }
+
+    #[test]
+    fn json_follows_the_schema() {
+        let journal = journal();
+        let json: Value = serde_json::from_str(&render(&journal, OutputFormat::Json, &Columns::default())).unwrap();
+        let keys: Vec<&str> = json[0].as_object().unwrap().keys().map(String::as_str).collect();
+        let names: Vec<&str> = Column::ALL.iter().map(|column| column.name()).collect();
+        assert_eq!(keys, names);
+        assert_eq!(json[0]["due"], Value::Null);
+        assert_eq!(json[1]["tags"], json!(["a", "b"]));
+        assert_eq!(json[1]["status"], json!("pending"));
+        assert_eq!(json[0]["source"], Value::Null);
+        assert_eq!(json[1]["source"], json!("~/work"));
+
+        let columns: Columns = "id,due".parse().unwrap();
+        let jsonl = render(&journal, OutputFormat::Jsonl, &columns);
+        assert_eq!(jsonl, "{\"id\":1,\"due\":null}\n{\"id\":2,\"due\":\"2026-10-20\"}\n");
+        assert!("id,colour".parse::<Columns>().is_err());
+    }
+
+    #[test]
+    fn csv_and_tsv_escape_cells() {
+        let journal = journal();
+        let columns: Columns = "id,text,tags".parse().unwrap();
+        let csv = render(&journal, OutputFormat::Csv, &columns);
+        assert_eq!(csv, "id,text,tags\r\n1,plain,\r\n2,\"say \"\"hi\"\", then\tleave\",\"a,b\"\r\n");
+        let tsv = render(&journal, OutputFormat::Tsv, &columns);
+        assert_eq!(tsv, "id\ttext\ttags\n1\tplain\t\n2\tsay \"hi\", then leave\ta,b\n");
+    }
+}
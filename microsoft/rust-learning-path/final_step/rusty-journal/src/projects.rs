@@ -0,0 +1,230 @@
+//! Project journals: a `.rusty-journal.json` in a directory holds the tasks
+//! for everything below it, found the way git finds `.git`.
+//!
+//! Every project journal that is created or used gets remembered in a
+//! registry, `$XDG_DATA_HOME/rusty-journal/projects.json` (falling back to
+//! `~/.local/share`), so `list --everywhere` can show them all next to the
+//! global journal.
+
+use crate::lock::JournalLock;
+use crate::output::{self, Columns, Entry, OutputFormat};
+use crate::storage::{self, Backend, JsonFile, Storage};
+use crate::table::{self, Colour, Terminal};
+use crate::tasks::{self, Journal, ListOptions};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The name of a project journal file.
+pub const FILE_NAME: &str = ".rusty-journal.json";
+
+/// The nearest project journal in `start` or one of its parents.
+pub fn find_project_journal(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|directory| directory.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Whether two paths name the same existing file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Create an empty project journal in `directory` and remember it.
+pub fn init(directory: &Path, lock_timeout: Duration) -> Result<()> {
+    let directory = directory.canonicalize()?;
+    let path = directory.join(FILE_NAME);
+    if path.exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("There already is a journal at {}", path.display()),
+        ));
+    }
+    JsonFile::new(&path).save(&Journal::default())?;
+    register(&path, lock_timeout)?;
+    println!("Created a project journal at {}.", path.display());
+    Ok(())
+}
+
+/// Remember a project journal for `list --everywhere`.
+pub fn register(journal: &Path, lock_timeout: Duration) -> Result<()> {
+    match registry_path() {
+        Some(registry) => add_to_registry(&registry, &journal.canonicalize()?, lock_timeout),
+        None => Ok(()),
+    }
+}
+
+/// Add a journal to the registry unless it's there already. The change is made
+/// under a lock, so commands running in two projects at once don't drop each
+/// other's entry.
+fn add_to_registry(registry: &Path, journal: &Path, lock_timeout: Duration) -> Result<()> {
+    if read_registry(registry)?.iter().any(|known| known == journal) {
+        return Ok(());
+    }
+    if let Some(parent) = registry.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _lock = JournalLock::acquire(registry, lock_timeout)?;
+    let mut journals = read_registry(registry)?;
+    if !journals.iter().any(|known| known == journal) {
+        journals.push(journal.to_path_buf());
+        write_registry(registry, &journals)?;
+    }
+    Ok(())
+}
+
+/// Print the tasks `options` picks from the global journal and every
+/// registered project journal, each labelled with where it came from.
+pub fn list_everywhere(
+    global: &Path,
+    backend: Option<Backend>,
+    lock_timeout: Duration,
+    options: &ListOptions,
+    format: OutputFormat,
+    columns: &Columns,
+) -> Result<()> {
+    let mut sources = vec![("global".to_string(), global.to_path_buf(), backend)];
+    if let Some(registry) = registry_path() {
+        for journal in read_registry(&registry)? {
+            if !journal.is_file() {
+                eprintln!("Skipping {}, which no longer exists.", journal.display());
+                continue;
+            }
+            if same_file(global, &journal) {
+                continue;
+            }
+            sources.push((label(&journal), journal, None));
+        }
+    }
+
+    // Each journal is only locked while it's read.
+    let mut journals = Vec::new();
+    for (label, path, backend) in sources {
+        let _lock = JournalLock::acquire(&path, lock_timeout)?;
+        let journal = storage::open(&path, backend)?.load()?;
+        journals.push((label, journal));
+    }
+
+    if format.is_machine() {
+        let open: Vec<HashSet<u64>> = journals.iter().map(|(_, journal)| journal.open_ids()).collect();
+        let entries: Vec<Entry> = journals
+            .iter()
+            .zip(&open)
+            .flat_map(|((label, journal), open)| {
+                tasks::select_tasks(journal, options).into_iter().map(move |task| Entry {
+                    task,
+                    open,
+                    source: Some(label.as_str()),
+                })
+            })
+            .collect();
+        output::print_entries(&entries, format, columns);
+        return Ok(());
+    }
+    let terminal = Terminal::stdout();
+    for (index, (label, journal)) in journals.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let heading = format!("== {} ==", label);
+        if terminal.colour {
+            println!("{}", table::paint(&heading, Colour::Bold));
+        } else {
+            println!("{}", heading);
+        }
+        tasks::print_list(journal, options, format, columns);
+    }
+    Ok(())
+}
+
+/// A project journal's directory, with the home directory shortened to `~`.
+fn label(journal: &Path) -> String {
+    let directory = journal.parent().unwrap_or(journal);
+    match home::home_dir().and_then(|home| directory.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => directory.display().to_string(),
+    }
+}
+
+fn registry_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home::home_dir().map(|home| home.join(".local").join("share")))?;
+    Some(base.join("rusty-journal").join("projects.json"))
+}
+
+fn read_registry(path: &Path) -> Result<Vec<PathBuf>> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_registry(path: &Path, journals: &[PathBuf]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    storage::write_atomically(path, |file| Ok(serde_json::to_writer_pretty(file, journals)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("rusty-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn the_nearest_project_journal_wins() {
+        let root = scratch("find");
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_journal(&nested), None);
+
+        fs::write(root.join(FILE_NAME), "").unwrap();
+        assert_eq!(find_project_journal(&nested), Some(root.join(FILE_NAME)));
+        fs::write(root.join("a").join(FILE_NAME), "").unwrap();
+        assert_eq!(find_project_journal(&nested), Some(root.join("a").join(FILE_NAME)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_registry_round_trips() {
+        let root = scratch("registry");
+        let registry = root.join("projects.json");
+        assert!(read_registry(&registry).unwrap().is_empty());
+
+        let journals = vec![root.join("one").join(FILE_NAME), root.join("two").join(FILE_NAME)];
+        write_registry(&registry, &journals).unwrap();
+        assert_eq!(read_registry(&registry).unwrap(), journals);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn concurrent_registrations_are_all_kept() {
+        let root = scratch("concurrent");
+        let registry = root.join("data").join("projects.json");
+        let journals: Vec<PathBuf> = (0..8).map(|n| root.join(n.to_string()).join(FILE_NAME)).collect();
+        std::thread::scope(|scope| {
+            for journal in &journals {
+                let registry = &registry;
+                scope.spawn(move || add_to_registry(registry, journal, Duration::from_secs(10)).unwrap());
+            }
+        });
+        let mut registered = read_registry(&registry).unwrap();
+        registered.sort();
+        assert_eq!(registered, journals);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
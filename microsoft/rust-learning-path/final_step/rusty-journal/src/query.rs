@@ -0,0 +1,627 @@
+//! A small filter language for `list`, e.g.
+//!
+//! ```text
+//! project:infra and (due < 2026-11-01 or +urgent) and not +blocked
+//! ```
+//!
+//! A query is built from filters joined with `and`, `or`, `not` and parentheses.
+//! Two filters next to each other mean `and`. The filters are:
+//!
+//! * `+tag` - the task has the tag.
+//! * `field:value` - shorthand for `field = value`.
+//! * `field OP value` - where `OP` is one of `=`, `!=`, `<`, `<=`, `>`, `>=`,
+//!   `contains` (case-insensitive substring) or `~` / `matches` (regex).
+//! * `"some text"` - shorthand for `text contains "some text"`.
+//!
+//! Fields are `id`, `parent`, `text`, `project`, `tag`, `status`, `priority`,
+//! `due`, `created` and `completed`. Dates are written `YYYY-MM-DD` or as
+//! `dates` reads them, e.g. `tomorrow`, `fri` or `"next month"`, and `none`
+//! matches a field that isn't set.
+
+use crate::dates;
+use crate::tasks::{Priority, Status, Task};
+use chrono::{Local, NaiveDate};
+use regex::{Regex, RegexBuilder};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed query, ready to be matched against tasks.
+#[derive(Debug, Clone)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    HasTag(String),
+    Equals(Field, Value),
+    Compare(Field, CmpOp, Value),
+    Contains(Field, String),
+    Matches(Field, Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Parent,
+    Text,
+    Project,
+    Tag,
+    Status,
+    Priority,
+    Due,
+    Created,
+    Completed,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Parent => "parent",
+            Field::Text => "text",
+            Field::Project => "project",
+            Field::Tag => "tag",
+            Field::Status => "status",
+            Field::Priority => "priority",
+            Field::Due => "due",
+            Field::Created => "created",
+            Field::Completed => "completed",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Field> {
+        let field = match name {
+            "id" => Field::Id,
+            "parent" => Field::Parent,
+            "text" | "description" => Field::Text,
+            "project" => Field::Project,
+            "tag" | "tags" => Field::Tag,
+            "status" => Field::Status,
+            "priority" => Field::Priority,
+            "due" => Field::Due,
+            "created" => Field::Created,
+            "completed" | "done" => Field::Completed,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn is_text(self) -> bool {
+        matches!(self, Field::Text | Field::Project | Field::Tag)
+    }
+
+    /// The values the field has on a task. Tags can give several; an unset
+    /// field gives `Value::None`.
+    fn values(self, task: &Task) -> Vec<Value> {
+        let local_date = |at: chrono::DateTime<chrono::Utc>| at.with_timezone(&Local).date_naive();
+        let value = match self {
+            Field::Id => Value::Number(task.id),
+            Field::Parent => task.parent.map_or(Value::None, Value::Number),
+            Field::Text => Value::Text(task.text.clone()),
+            Field::Project => task.project.clone().map_or(Value::None, Value::Text),
+            Field::Tag if task.tags.is_empty() => Value::None,
+            Field::Tag => return task.tags.iter().cloned().map(Value::Text).collect(),
+            Field::Status => Value::Status(task.status),
+            Field::Priority => task.priority.map_or(Value::None, Value::Priority),
+            Field::Due => task.due.map_or(Value::None, Value::Date),
+            Field::Created => Value::Date(local_date(task.created_at)),
+            Field::Completed => task.completed_at.map_or(Value::None, |at| Value::Date(local_date(at))),
+        };
+        vec![value]
+    }
+}
+
+/// A literal from the query. Comparisons only ever pit values of the same kind
+/// against each other, so the derived ordering is enough.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Value {
+    None,
+    Number(u64),
+    Text(String),
+    Status(Status),
+    Priority(Priority),
+    Date(NaiveDate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Matches,
+}
+
+impl CmpOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "=",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+            CmpOp::Contains => "contains",
+            CmpOp::Matches => "~",
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Query, QueryError> {
+        let tokens = lex(query)?;
+        let mut parser = Parser { query, tokens, pos: 0 };
+        let parsed = parser.parse_or()?;
+
+        // Anything left over is a mistake, most likely an unbalanced `)`.
+        let token = parser.peek();
+        if token.kind != TokenKind::End {
+            let message = format!("unexpected {}", token.describe());
+            return Err(parser.error(token, message));
+        }
+        Ok(parsed)
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Query::And(left, right) => left.matches(task) && right.matches(task),
+            Query::Or(left, right) => left.matches(task) || right.matches(task),
+            Query::Not(inner) => !inner.matches(task),
+            Query::HasTag(tag) => task.tags.contains(tag),
+            Query::Equals(field, value) => field.values(task).contains(value),
+            Query::Compare(field, op, value) => field.values(task).iter().any(|actual| {
+                // Unset fields are neither before nor after anything.
+                if *actual == Value::None || *value == Value::None {
+                    return false;
+                }
+                match op {
+                    CmpOp::Lt => actual < value,
+                    CmpOp::Le => actual <= value,
+                    CmpOp::Gt => actual > value,
+                    CmpOp::Ge => actual >= value,
+                    _ => unreachable!("only ordering operators build Query::Compare"),
+                }
+            }),
+            Query::Contains(field, needle) => field.values(task).iter().any(|actual| match actual {
+                Value::Text(text) => text.to_lowercase().contains(needle),
+                _ => false,
+            }),
+            Query::Matches(field, regex) => field.values(task).iter().any(|actual| match actual {
+                Value::Text(text) => regex.is_match(text),
+                _ => false,
+            }),
+        }
+    }
+}
+
+/// A query that couldn't be parsed, with the columns it went wrong at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub query: String,
+    /// 1-based column of the first offending character.
+    pub column: usize,
+    /// How many characters to underline.
+    pub width: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid query at column {}: {}", self.column, self.message)?;
+        writeln!(f, "  {}", self.query)?;
+        write!(f, "  {}{}", " ".repeat(self.column - 1), "^".repeat(self.width.max(1)))
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Op(CmpOp),
+    Word(String),
+    Str(String),
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// Character offsets, end exclusive.
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Op(op) => format!("'{}'", op.symbol()),
+            TokenKind::Word(word) => format!("'{}'", word),
+            TokenKind::Str(text) => format!("\"{}\"", text),
+            TokenKind::End => "end of query".to_string(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn lex(query: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = query.chars().collect();
+    let error = |start: usize, end: usize, message: String| QueryError {
+        query: query.to_string(),
+        column: start + 1,
+        width: end - start,
+        message,
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '<' if next == Some('=') => TokenKind::Op(CmpOp::Le),
+            '>' if next == Some('=') => TokenKind::Op(CmpOp::Ge),
+            '!' if next == Some('=') => TokenKind::Op(CmpOp::Ne),
+            '=' if next == Some('=') => TokenKind::Op(CmpOp::Eq),
+            '<' => TokenKind::Op(CmpOp::Lt),
+            '>' => TokenKind::Op(CmpOp::Gt),
+            '=' => TokenKind::Op(CmpOp::Eq),
+            '~' => TokenKind::Op(CmpOp::Matches),
+            '"' | '\'' => {
+                // A quoted string. A backslash only escapes the quote itself or
+                // another backslash, so regexes like '\d+' can be written as is.
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(start, i, "unterminated string".to_string())),
+                        Some(&q) if q == c => break,
+                        Some('\\') if chars.get(i + 1).is_some_and(|&n| n == c || n == '\\') => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&other) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Str(text)
+            }
+            _ => {
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !"()<>=!~\"'".contains(chars[i])
+                {
+                    i += 1;
+                }
+                if i == start {
+                    return Err(error(start, start + 1, format!("unexpected character '{}'", c)));
+                }
+                let word = chars[start..i].iter().collect();
+                tokens.push(Token { kind: TokenKind::Word(word), start, end: i });
+                continue;
+            }
+        };
+
+        // Advance past the punctuation or the closing quote.
+        i += match kind {
+            TokenKind::Op(CmpOp::Le | CmpOp::Ge | CmpOp::Ne) => 2,
+            TokenKind::Op(CmpOp::Eq) if next == Some('=') => 2,
+            _ => 1,
+        };
+        tokens.push(Token { kind, start, end: i });
+    }
+
+    tokens.push(Token { kind: TokenKind::End, start: chars.len(), end: chars.len() + 1 });
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    query: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Token {
+        self.tokens[self.pos].clone()
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek();
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, token: Token, message: String) -> QueryError {
+        QueryError {
+            query: self.query.to_string(),
+            column: token.start + 1,
+            width: token.end - token.start,
+            message,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.peek().is_keyword("or") {
+            self.next();
+            let right = self.parse_and()?;
+            left = Query::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let token = self.peek();
+            if token.is_keyword("and") {
+                self.next();
+            } else if token.is_keyword("or")
+                || matches!(token.kind, TokenKind::RParen | TokenKind::End)
+            {
+                break;
+            }
+            // Anything else starts another filter, joined with an implicit `and`.
+            let right = self.parse_unary()?;
+            left = Query::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        if self.peek().is_keyword("not") {
+            self.next();
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Query, QueryError> {
+        let token = self.next();
+        match &token.kind {
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                let closing = self.next();
+                if closing.kind != TokenKind::RParen {
+                    let message = format!(
+                        "expected ')' to close the '(' at column {}, found {}",
+                        token.start + 1,
+                        closing.describe()
+                    );
+                    return Err(self.error(closing, message));
+                }
+                Ok(inner)
+            }
+            TokenKind::Str(text) => Ok(Query::Contains(Field::Text, text.to_lowercase())),
+            TokenKind::Word(word) if word.starts_with('+') => {
+                let tag = &word[1..];
+                if tag.is_empty() {
+                    return Err(self.error(token.clone(), "expected a tag name after '+'".to_string()));
+                }
+                Ok(Query::HasTag(tag.to_string()))
+            }
+            TokenKind::Word(word) if word.contains(':') => {
+                // `field:value`, where the value may also be a quoted string.
+                let (name, value) = word.split_once(':').unwrap();
+                let name_token = Token { end: token.start + name.chars().count(), ..token.clone() };
+                let field = self.field(name, name_token)?;
+                let value_token = if value.is_empty() {
+                    match self.peek().kind {
+                        TokenKind::Str(_) => self.next(),
+                        _ => {
+                            let message = format!("expected a value after '{}:'", name);
+                            return Err(self.error(token.clone(), message));
+                        }
+                    }
+                } else {
+                    let start = token.start + name.chars().count() + 1;
+                    Token { kind: TokenKind::Word(value.to_string()), start, end: token.end }
+                };
+                self.build(field, CmpOp::Eq, token.clone(), value_token)
+            }
+            TokenKind::Word(word) if Field::from_name(&word.to_lowercase()).is_some() => {
+                let field = self.field(&word.to_lowercase(), token.clone())?;
+                let op_token = self.next();
+                let op = match &op_token.kind {
+                    TokenKind::Op(op) => *op,
+                    _ if op_token.is_keyword("contains") => CmpOp::Contains,
+                    _ if op_token.is_keyword("matches") => CmpOp::Matches,
+                    _ => {
+                        let message = format!(
+                            "expected an operator after '{}' (=, !=, <, <=, >, >=, contains, ~), found {}",
+                            word,
+                            op_token.describe()
+                        );
+                        return Err(self.error(op_token, message));
+                    }
+                };
+                let value_token = self.next();
+                if !matches!(value_token.kind, TokenKind::Word(_) | TokenKind::Str(_)) {
+                    let message = format!(
+                        "expected a value after '{}', found {}",
+                        op.symbol(),
+                        value_token.describe()
+                    );
+                    return Err(self.error(value_token, message));
+                }
+                self.build(field, op, op_token, value_token)
+            }
+            TokenKind::Word(word) => {
+                let message = format!(
+                    "unknown field or filter '{}', expected +tag, field:value or field < value",
+                    word
+                );
+                Err(self.error(token.clone(), message))
+            }
+            _ => {
+                let message = format!("expected a filter, found {}", token.describe());
+                Err(self.error(token.clone(), message))
+            }
+        }
+    }
+
+    fn field(&self, name: &str, token: Token) -> Result<Field, QueryError> {
+        Field::from_name(&name.to_lowercase()).ok_or_else(|| {
+            let message = format!(
+                "unknown field '{}', expected one of id, parent, text, project, tag, status, priority, due, created, completed",
+                name
+            );
+            self.error(token, message)
+        })
+    }
+
+    /// Turn `field op value` into a query, checking that the operator and the
+    /// value make sense for the field.
+    fn build(&self, field: Field, op: CmpOp, op_token: Token, value_token: Token) -> Result<Query, QueryError> {
+        let raw = match &value_token.kind {
+            TokenKind::Word(word) | TokenKind::Str(word) => word.clone(),
+            _ => unreachable!("callers only pass words and strings as values"),
+        };
+
+        match op {
+            CmpOp::Contains | CmpOp::Matches if !field.is_text() => {
+                let message = format!(
+                    "'{}' only works on text fields (text, project, tag), not '{}'",
+                    op.symbol(),
+                    field.name()
+                );
+                Err(self.error(op_token, message))
+            }
+            CmpOp::Contains => Ok(Query::Contains(field, raw.to_lowercase())),
+            CmpOp::Matches => RegexBuilder::new(&raw)
+                .case_insensitive(true)
+                .build()
+                .map(|regex| Query::Matches(field, regex))
+                .map_err(|e| {
+                    let reason = e.to_string();
+                    let reason = reason.lines().last().unwrap_or_default().trim_start_matches("error: ");
+                    self.error(value_token.clone(), format!("invalid regular expression: {}", reason))
+                }),
+            CmpOp::Lt | CmpOp::Le | CmpOp::Gt | CmpOp::Ge
+                if field.is_text() || field == Field::Status =>
+            {
+                let message = format!("'{}' can't be used with the '{}' field", op.symbol(), field.name());
+                Err(self.error(op_token, message))
+            }
+            _ => {
+                let value = self.value(field, &raw, value_token)?;
+                Ok(match op {
+                    CmpOp::Eq => Query::Equals(field, value),
+                    CmpOp::Ne => Query::Not(Box::new(Query::Equals(field, value))),
+                    _ => Query::Compare(field, op, value),
+                })
+            }
+        }
+    }
+
+    fn value(&self, field: Field, raw: &str, token: Token) -> Result<Value, QueryError> {
+        if raw.eq_ignore_ascii_case("none") && field != Field::Text {
+            return Ok(Value::None);
+        }
+        let value = match field {
+            Field::Text | Field::Project | Field::Tag => Some(Value::Text(raw.to_string())),
+            Field::Id | Field::Parent => raw.parse().ok().map(Value::Number),
+            Field::Priority => Priority::from_str(raw).ok().map(Value::Priority),
+            Field::Status => match raw.to_lowercase().as_str() {
+                "pending" | "open" => Some(Value::Status(Status::Pending)),
+                "completed" | "done" => Some(Value::Status(Status::Completed)),
+                _ => None,
+            },
+            Field::Due | Field::Created | Field::Completed => parse_date(raw).map(Value::Date),
+        };
+
+        value.ok_or_else(|| {
+            let expected = match field {
+                Field::Id | Field::Parent => "a task ID",
+                Field::Priority => "a priority (high, medium, low)",
+                Field::Status => "a status (pending, completed)",
+                _ => "a date like 2026-11-01, today, tomorrow or eom",
+            };
+            self.error(token, format!("expected {} for '{}', found '{}'", expected, field.name(), raw))
+        })
+    }
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    dates::parse(raw, Local::now().date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(text: &str, project: Option<&str>, tags: &[&str], due: Option<&str>) -> Task {
+        let mut task = Task::new(text.to_string());
+        task.project = project.map(str::to_string);
+        task.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        task.due = due.map(|due| due.parse().unwrap());
+        task
+    }
+
+    #[test]
+    fn evaluates_nested_expression() {
+        let query =
+            Query::parse("project:infra and (due < 2026-11-01 or +urgent) and not +blocked").unwrap();
+
+        assert!(query.matches(&task("a", Some("infra"), &[], Some("2026-10-20"))));
+        assert!(query.matches(&task("b", Some("infra"), &["urgent"], None)));
+        assert!(!query.matches(&task("c", Some("infra"), &["urgent", "blocked"], None)));
+        assert!(!query.matches(&task("d", Some("home"), &["urgent"], None)));
+        assert!(!query.matches(&task("e", Some("infra"), &[], Some("2026-12-01"))));
+    }
+
+    #[test]
+    fn text_contains_and_regex() {
+        let milk = task("Buy milk", None, &[], None);
+        assert!(Query::parse("text contains MILK").unwrap().matches(&milk));
+        assert!(Query::parse("text ~ '^buy\\s'").unwrap().matches(&milk));
+        assert!(!Query::parse("text matches 'milk$' and project != none").unwrap().matches(&milk));
+    }
+
+    #[test]
+    fn priority_comparisons() {
+        let mut urgent = task("a", None, &[], None);
+        urgent.priority = Some(Priority::High);
+        assert!(Query::parse("priority >= medium").unwrap().matches(&urgent));
+        assert!(!Query::parse("priority < medium").unwrap().matches(&urgent));
+        assert!(!Query::parse("priority >= low").unwrap().matches(&task("b", None, &[], None)));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_column() {
+        let error = Query::parse("project:infra and (due < tomorow or +urgent)").unwrap_err();
+        assert_eq!(error.column, 26);
+        assert_eq!(error.width, 7);
+
+        let error = Query::parse("(+a or +b").unwrap_err();
+        assert_eq!(error.column, 10);
+        assert!(error.message.contains("expected ')'"));
+
+        let error = Query::parse("due contains 2026").unwrap_err();
+        assert_eq!(error.column, 5);
+
+        let error = Query::parse("size:3").unwrap_err();
+        assert_eq!((error.column, error.width), (1, 4));
+    }
+}
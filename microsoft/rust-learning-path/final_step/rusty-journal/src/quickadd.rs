@@ -0,0 +1,208 @@
+//! Task fields written inline in the text given to `add`, e.g.
+//!
+//! ```text
+//! deploy api due:fri p:H +release project:infra every:week
+//! ```
+//!
+//! The recognised words are taken out of the text:
+//!
+//! * `+tag`
+//! * `due:DATE`, where the date can be several words, see `dates`:
+//!   `due:next monday`, `due:em 3 dias`
+//! * `p:H`, `pri:` or `priority:` with high, medium or low
+//! * `project:NAME`
+//! * `every:week`, `every:3d` or `recur:RULE`, as `add --recur` takes
+//!
+//! A word starting with `\` is kept as text, without the backslash, so
+//! `\+1` adds "+1" to the text rather than a tag.
+
+use crate::config;
+use crate::dates;
+use crate::recurrence::Recurrence;
+use crate::tasks::{parse_project, parse_tag, Priority, Task};
+use chrono::NaiveDate;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// The longest date, in words, looked for after `due:`.
+const MAX_DATE_WORDS: usize = 4;
+
+/// A task line with its inline fields read out of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuickAdd {
+    pub text: String,
+    pub due: Option<NaiveDate>,
+    pub priority: Option<Priority>,
+    pub tags: BTreeSet<String>,
+    pub project: Option<String>,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl QuickAdd {
+    /// Read the fields out of `line`, with dates relative to `today`. The
+    /// text keeps its own spacing; only the recognised words, and the space
+    /// before each, are cut out of it.
+    pub fn parse(line: &str, today: NaiveDate) -> Result<QuickAdd, String> {
+        let words = words(line);
+        let mut parsed = QuickAdd::default();
+        // The byte ranges of `line` that aren't text.
+        let mut removed: Vec<Range<usize>> = Vec::new();
+        let mut index = 0;
+        while index < words.len() {
+            let (start, word) = words[index];
+            let gap = index.checked_sub(1).map_or(0, |previous| end_of(words[previous]));
+            index += 1;
+            if word.starts_with('\\') {
+                removed.push(start..start + 1);
+                continue;
+            }
+            // `+1` is more likely a vote than a tag.
+            if word.len() > 1 && word.starts_with('+') && !word[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                parsed.tags.insert(parse_tag(word)?);
+                removed.push(gap..end_of(words[index - 1]));
+                continue;
+            }
+            let Some((key, value)) = word.split_once(':') else {
+                continue;
+            };
+            match key.to_lowercase().as_str() {
+                "due" => {
+                    // Take as many of the following words as still make a date.
+                    let most = MAX_DATE_WORDS.min(words.len() - index + 1);
+                    let (due, used) = (1..=most)
+                        .rev()
+                        .find_map(|count| {
+                            let phrase = std::iter::once(value)
+                                .chain(words[index..index + count - 1].iter().map(|&(_, word)| word))
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            dates::parse(&phrase, today).map(|due| (due, count - 1))
+                        })
+                        .ok_or_else(|| format!("Unknown date in '{}', expected e.g. due:fri or due:2026-11-01", word))?;
+                    parsed.due = Some(due);
+                    index += used;
+                }
+                "p" | "pri" | "priority" => parsed.priority = Some(value.parse()?),
+                "project" => parsed.project = Some(parse_project(value)?),
+                "every" => parsed.recurrence = Some(word.parse()?),
+                "recur" => parsed.recurrence = Some(value.parse()?),
+                _ => continue,
+            }
+            removed.push(gap..end_of(words[index - 1]));
+        }
+
+        let mut text = String::new();
+        let mut kept_from = 0;
+        for range in removed {
+            text.push_str(&line[kept_from..range.start]);
+            kept_from = range.end;
+        }
+        text.push_str(&line[kept_from..]);
+        parsed.text = text.trim().to_string();
+        if parsed.text.is_empty() {
+            return Err(format!("'{}' has no text left once its fields are taken out", line));
+        }
+        Ok(parsed)
+    }
+
+    /// The task to add. A recurring task needs a due date to count from, so
+    /// it gets its first one on or after `today` if it has none.
+    pub fn into_task(self, today: NaiveDate) -> Task {
+        let mut task = Task::new(self.text);
+        task.due = match &self.recurrence {
+            Some(recurrence) => Some(self.due.unwrap_or_else(|| recurrence.first_on_or_after(today))),
+            None => self.due,
+        };
+        task.priority = self.priority;
+        task.tags = self.tags;
+        task.project = self.project;
+        task.recurrence = self.recurrence;
+        task
+    }
+}
+
+/// The words of `line`, each with the byte it starts at.
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(index),
+            (Some(from), true) => {
+                words.push((from, &line[from..index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn end_of((start, word): (usize, &str)) -> usize {
+    start + word.len()
+}
+
+/// Show the fields of a task about to be added, for `add --dry-run`.
+pub fn print_parsed(task: &Task) {
+    let date_format = &config::appearance().date_format;
+    println!("text:     {}", task.text);
+    if let Some(due) = task.due {
+        println!("due:      {} ({})", due.format(date_format), due.format("%A"));
+    }
+    if let Some(priority) = task.priority {
+        println!("priority: {}", priority);
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+        println!("tags:     {}", tags.join(" "));
+    }
+    if let Some(project) = &task.project {
+        println!("project:  {}", project);
+    }
+    if let Some(recurrence) = &task.recurrence {
+        println!("recurs:   {}", recurrence);
+    }
+    if let Some(parent) = task.parent {
+        println!("parent:   {}", parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        // A Sunday.
+        "2026-10-18".parse().unwrap()
+    }
+
+    #[test]
+    fn fields_are_taken_out_of_the_text() {
+        let parsed = QuickAdd::parse("deploy api due:fri p:H +release project:infra every:week", today()).unwrap();
+        assert_eq!(parsed.text, "deploy api");
+        assert_eq!(parsed.due, "2026-10-23".parse().ok());
+        assert_eq!(parsed.priority, Some(Priority::High));
+        assert_eq!(parsed.tags, BTreeSet::from(["release".to_string()]));
+        assert_eq!(parsed.project.as_deref(), Some("infra"));
+        assert_eq!(parsed.recurrence, "every:week".parse().ok());
+    }
+
+    #[test]
+    fn dates_can_take_several_words() {
+        let parsed = QuickAdd::parse("call mom due:next monday about lunch", today()).unwrap();
+        assert_eq!((parsed.text.as_str(), parsed.due), ("call mom about lunch", "2026-10-19".parse().ok()));
+        let parsed = QuickAdd::parse("pagar conta due:daqui a 3 dias", today()).unwrap();
+        assert_eq!((parsed.text.as_str(), parsed.due), ("pagar conta", "2026-10-21".parse().ok()));
+    }
+
+    #[test]
+    fn other_words_stay_text() {
+        let parsed = QuickAdd::parse("reply +1 at 10:30 \\+fun", today()).unwrap();
+        assert_eq!(parsed.text, "reply +1 at 10:30 +fun");
+        assert!(parsed.tags.is_empty());
+        let parsed = QuickAdd::parse("  step 1:\tmix  +baking  due:fri  well  ", today()).unwrap();
+        assert_eq!(parsed.text, "step 1:\tmix  well");
+        assert!(QuickAdd::parse("ship due:someday", today()).is_err());
+        assert!(QuickAdd::parse("+only p:h", today()).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How often a task repeats.
+///
+/// This is the subset of iCalendar RRULEs (RFC 5545) the journal understands,
+/// and it is stored in the journal in that form, e.g. `FREQ=WEEKLY;BYDAY=MO`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// Repeat every `interval` days/weeks/months/years.
+    pub interval: u32,
+    /// For weekly rules, the weekdays to repeat on. Empty means the due date's weekday.
+    pub weekdays: Vec<Weekday>,
+    /// For monthly rules, the day of the month; -1 is the last day. `None`
+    /// means the due date's day.
+    pub month_day: Option<i32>,
+    /// No occurrences after this date.
+    pub until: Option<NaiveDate>,
+    /// How many occurrences are left, counting the current one.
+    pub count: Option<u32>,
+}
+
+/// The longest interval a rule may have, in its own units.
+const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Recurrence {
+    fn new(frequency: Frequency) -> Recurrence {
+        Recurrence {
+            frequency,
+            interval: 1,
+            weekdays: Vec::new(),
+            month_day: None,
+            until: None,
+            count: None,
+        }
+    }
+
+    /// The first occurrence on or after `date`, used as the due date of a new
+    /// recurring task that wasn't given one.
+    pub fn first_on_or_after(&self, date: NaiveDate) -> NaiveDate {
+        let matches_rule = match self.frequency {
+            Frequency::Weekly => self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()),
+            Frequency::Monthly => self.month_day.is_none_or(|day| resolve_month_day(date, day) == date),
+            _ => true,
+        };
+        if matches_rule {
+            date
+        } else {
+            // Step from the day before with a plain rule, so the interval doesn't apply.
+            let single = Recurrence { interval: 1, until: None, count: None, ..self.clone() };
+            date.pred_opt().and_then(|before| single.step(before)).unwrap_or(date)
+        }
+    }
+
+    /// The occurrence after the one due on `due`, or `None` if the rule has run out.
+    pub fn next_after(&self, due: NaiveDate) -> Option<NaiveDate> {
+        if self.count.is_some_and(|count| count <= 1) {
+            return None;
+        }
+        let next = self.step(due)?;
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+
+    /// The rule to give the next occurrence: the same, with one fewer left.
+    pub fn for_next(&self) -> Recurrence {
+        Recurrence { count: self.count.map(|count| count - 1), ..self.clone() }
+    }
+
+    /// The next occurrence, or `None` past the last date chrono can represent.
+    fn step(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let interval = self.interval.max(1);
+        match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::days(interval.into())),
+            Frequency::Weekly if self.weekdays.is_empty() => date.checked_add_signed(Duration::weeks(interval.into())),
+            Frequency::Weekly => {
+                // A later listed weekday in the same week, or else the first one
+                // `interval` weeks on. Weeks start on Monday.
+                let today = date.weekday().num_days_from_monday();
+                let mut days: Vec<u32> = self.weekdays.iter().map(|day| day.num_days_from_monday()).collect();
+                days.sort_unstable();
+                match days.iter().find(|&&day| day > today) {
+                    Some(&day) => date.checked_add_signed(Duration::days((day - today).into())),
+                    None => {
+                        let monday = date - Duration::days(today.into());
+                        monday.checked_add_signed(Duration::weeks(interval.into()) + Duration::days(days[0].into()))
+                    }
+                }
+            }
+            Frequency::Monthly => match self.month_day {
+                Some(day) if resolve_month_day(date, day) > date => Some(resolve_month_day(date, day)),
+                Some(day) => add_months(date, interval).map(|date| resolve_month_day(date, day)),
+                None => add_months(date, interval),
+            },
+            Frequency::Yearly => add_months(date, interval.checked_mul(12)?),
+        }
+    }
+
+    /// The rule as an iCalendar RRULE value, e.g. `FREQ=MONTHLY;BYMONTHDAY=5`.
+    pub fn to_rrule(&self) -> String {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        let mut parts = vec![format!("FREQ={}", frequency)];
+        if self.interval > 1 {
+            parts.push(format!("INTERVAL={}", self.interval));
+        }
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|day| ical_weekday(*day)).collect();
+            parts.push(format!("BYDAY={}", days.join(",")));
+        }
+        if let Some(day) = self.month_day {
+            parts.push(format!("BYMONTHDAY={}", day));
+        }
+        if let Some(until) = self.until {
+            parts.push(format!("UNTIL={}", until.format("%Y%m%d")));
+        }
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+        parts.join(";")
+    }
+
+    fn parse_rrule(rule: &str) -> Result<Recurrence, String> {
+        let mut frequency = None;
+        let mut recurrence = Recurrence::new(Frequency::Daily);
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid RRULE part '{}'", part))?;
+            let number = || value.parse().map_err(|_| format!("Invalid number in RRULE part '{}'", part));
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported RRULE frequency '{}'", value)),
+                    })
+                }
+                "INTERVAL" => recurrence.interval = number()?,
+                "COUNT" => recurrence.count = Some(number()?),
+                "BYMONTHDAY" => recurrence.month_day = Some(value.parse().map_err(|_| format!("Invalid BYMONTHDAY '{}'", value))?),
+                "BYDAY" => recurrence.weekdays = parse_weekdays(value)?,
+                "UNTIL" => {
+                    // Either a date or a date-time; only the date matters to us.
+                    let date = value.get(..8).unwrap_or(value);
+                    recurrence.until = Some(
+                        NaiveDate::parse_from_str(date, "%Y%m%d")
+                            .map_err(|_| format!("Invalid UNTIL date '{}'", value))?,
+                    );
+                }
+                "WKST" => {}
+                _ => return Err(format!("Unsupported RRULE part '{}'", part)),
+            }
+        }
+        recurrence.frequency = frequency.ok_or("An RRULE needs a FREQ")?;
+        recurrence.validate()?;
+        Ok(recurrence)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.interval == 0 {
+            return Err("The interval must be at least 1".to_string());
+        }
+        if self.interval > MAX_INTERVAL {
+            return Err(format!("The interval can be at most {}", MAX_INTERVAL));
+        }
+        if self.count == Some(0) {
+            return Err("COUNT must be at least 1".to_string());
+        }
+        if !self.weekdays.is_empty() && self.frequency != Frequency::Weekly {
+            return Err("Weekdays only work with weekly rules".to_string());
+        }
+        match self.month_day {
+            Some(day) if self.frequency != Frequency::Monthly => {
+                Err(format!("A day of the month ({}) only works with monthly rules", day))
+            }
+            Some(day) if day == 0 || !(-1..=31).contains(&day) => {
+                Err(format!("Invalid day of the month {}, expected 1-31 or -1 for the last day", day))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Accepts the short forms used on the command line as well as RRULEs:
+///
+/// * `daily`, `weekly`, `monthly`, `yearly`
+/// * `weekly:mon,thu` and `monthly:5` (`monthly:-1` is the last day)
+/// * `every:day`, `every:week`, `every:3d`, `every:2w`, `every:6m`, `every:1y`, `every:monday`
+/// * `FREQ=WEEKLY;BYDAY=MO,TH`, optionally prefixed with `RRULE:`
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_uppercase();
+        if let Some(rule) = upper.strip_prefix("RRULE:") {
+            return Recurrence::parse_rrule(rule);
+        }
+        if upper.starts_with("FREQ=") {
+            return Recurrence::parse_rrule(&upper);
+        }
+
+        let lower = s.to_lowercase();
+        let (kind, argument) = match lower.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (lower.as_str(), None),
+        };
+
+        let recurrence = match (kind, argument) {
+            ("daily", None) => Recurrence::new(Frequency::Daily),
+            ("weekly", None) => Recurrence::new(Frequency::Weekly),
+            ("monthly", None) => Recurrence::new(Frequency::Monthly),
+            ("yearly", None) => Recurrence::new(Frequency::Yearly),
+            ("weekly", Some(days)) => Recurrence {
+                weekdays: parse_weekdays(days)?,
+                ..Recurrence::new(Frequency::Weekly)
+            },
+            ("monthly", Some(day)) => Recurrence {
+                month_day: Some(day.parse().map_err(|_| format!("Invalid day of the month '{}'", day))?),
+                ..Recurrence::new(Frequency::Monthly)
+            },
+            ("every", Some(every)) => parse_every(every)?,
+            _ => {
+                return Err(format!(
+                    "Unknown recurrence '{}', expected e.g. daily, weekly:mon,thu, monthly:5, every:3d or an RRULE",
+                    s
+                ))
+            }
+        };
+        recurrence.validate()?;
+        Ok(recurrence)
+    }
+}
+
+/// A short human description, in the same syntax `FromStr` accepts when possible.
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.until.is_some() || self.count.is_some() {
+            return write!(f, "{}", self.to_rrule());
+        }
+        let unit = match self.frequency {
+            Frequency::Daily => "d",
+            Frequency::Weekly => "w",
+            Frequency::Monthly => "m",
+            Frequency::Yearly => "y",
+        };
+        match (self.frequency, self.interval, self.weekdays.is_empty(), self.month_day) {
+            (Frequency::Weekly, 1, false, _) => {
+                let days: Vec<String> = self.weekdays.iter().map(|day| day.to_string().to_lowercase()).collect();
+                write!(f, "weekly:{}", days.join(","))
+            }
+            (Frequency::Monthly, 1, _, Some(day)) => write!(f, "monthly:{}", day),
+            (Frequency::Daily, 1, ..) => write!(f, "daily"),
+            (Frequency::Weekly, 1, ..) => write!(f, "weekly"),
+            (Frequency::Monthly, 1, ..) => write!(f, "monthly"),
+            (Frequency::Yearly, 1, ..) => write!(f, "yearly"),
+            (_, interval, true, None) => write!(f, "every:{}{}", interval, unit),
+            _ => write!(f, "{}", self.to_rrule()),
+        }
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        Recurrence::parse_rrule(&rule)
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(recurrence: Recurrence) -> String {
+        recurrence.to_rrule()
+    }
+}
+
+fn parse_every(every: &str) -> Result<Recurrence, String> {
+    let named = match every {
+        "day" => Some(Recurrence::new(Frequency::Daily)),
+        "week" => Some(Recurrence::new(Frequency::Weekly)),
+        "month" => Some(Recurrence::new(Frequency::Monthly)),
+        "year" => Some(Recurrence::new(Frequency::Yearly)),
+        _ => None,
+    };
+    if let Some(recurrence) = named {
+        return Ok(recurrence);
+    }
+    if let Ok(weekdays) = parse_weekdays(every) {
+        return Ok(Recurrence { weekdays, ..Recurrence::new(Frequency::Weekly) });
+    }
+
+    // `3d`, `2w`, `6m`, `1y`.
+    let split = every.find(|c: char| !c.is_ascii_digit()).unwrap_or(every.len());
+    let (number, unit) = every.split_at(split);
+    let interval = number
+        .parse()
+        .map_err(|_| format!("Invalid interval 'every:{}', expected e.g. every:3d or every:2w", every))?;
+    let frequency = match unit {
+        "d" | "day" | "days" => Frequency::Daily,
+        "w" | "week" | "weeks" => Frequency::Weekly,
+        "m" | "month" | "months" => Frequency::Monthly,
+        "y" | "year" | "years" => Frequency::Yearly,
+        _ => return Err(format!("Unknown unit '{}' in 'every:{}', expected d, w, m or y", unit, every)),
+    };
+    Ok(Recurrence { interval, ..Recurrence::new(frequency) })
+}
+
+/// Weekdays as `mon,thu`, `monday`, or iCalendar's `MO,TH`.
+fn parse_weekdays(days: &str) -> Result<Vec<Weekday>, String> {
+    days.split(',')
+        .map(|day| {
+            let day = day.trim().to_lowercase();
+            let weekday = match day.as_str() {
+                "mo" => Some(Weekday::Mon),
+                "tu" => Some(Weekday::Tue),
+                "we" => Some(Weekday::Wed),
+                "th" => Some(Weekday::Thu),
+                "fr" => Some(Weekday::Fri),
+                "sa" => Some(Weekday::Sat),
+                "su" => Some(Weekday::Sun),
+                _ => day.parse().ok(),
+            };
+            weekday.ok_or_else(|| format!("Unknown weekday '{}'", day))
+        })
+        .collect()
+}
+
+fn ical_weekday(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// Add months, keeping the day of the month where the month is long enough
+/// and using its last day otherwise.
+fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    date.checked_add_months(Months::new(months))
+}
+
+/// The given day in `date`'s month, clamped to the month's length; -1 is the last day.
+fn resolve_month_day(date: NaiveDate, day: i32) -> NaiveDate {
+    let first = date.with_day(1).unwrap();
+    let last = add_months(first, 1).and_then(|next| next.pred_opt()).unwrap_or(NaiveDate::MAX);
+    if day < 0 {
+        return last;
+    }
+    first.with_day(day as u32).unwrap_or(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn short_forms_and_rrules_agree() {
+        let short: Recurrence = "weekly:mon,thu".parse().unwrap();
+        let rrule: Recurrence = "RRULE:FREQ=WEEKLY;BYDAY=MO,TH".parse().unwrap();
+        assert_eq!(short, rrule);
+        assert_eq!(short.to_rrule(), "FREQ=WEEKLY;BYDAY=MO,TH");
+        assert_eq!(short.to_string(), "weekly:mon,thu");
+        assert_eq!("every:3d".parse::<Recurrence>().unwrap().to_rrule(), "FREQ=DAILY;INTERVAL=3");
+        assert!("monthly:32".parse::<Recurrence>().is_err());
+        assert!("fortnightly".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn next_occurrences() {
+        // 2026-10-19 is a Monday.
+        let weekly: Recurrence = "weekly:mon,thu".parse().unwrap();
+        assert_eq!(weekly.next_after(date("2026-10-19")), Some(date("2026-10-22")));
+        assert_eq!(weekly.next_after(date("2026-10-22")), Some(date("2026-10-26")));
+
+        let monthly: Recurrence = "monthly:31".parse().unwrap();
+        assert_eq!(monthly.next_after(date("2026-10-31")), Some(date("2026-11-30")));
+        let invoice: Recurrence = "monthly:5".parse().unwrap();
+        assert_eq!(invoice.first_on_or_after(date("2026-10-18")), date("2026-11-05"));
+
+        let every: Recurrence = "every:3d".parse().unwrap();
+        assert_eq!(every.next_after(date("2026-12-30")), Some(date("2027-01-02")));
+
+        let twice: Recurrence = "FREQ=DAILY;COUNT=2".parse().unwrap();
+        assert_eq!(twice.next_after(date("2026-10-19")), Some(date("2026-10-20")));
+        assert_eq!(twice.for_next().next_after(date("2026-10-20")), None);
+    }
+
+    #[test]
+    fn intervals_are_capped() {
+        assert!("every:4000000000d".parse::<Recurrence>().is_err());
+        assert!("every:1001y".parse::<Recurrence>().is_err());
+        // The largest interval runs out near the end of chrono's dates instead of panicking.
+        for frequency in ["d", "w", "m", "y"] {
+            let rule: Recurrence = format!("every:{}{}", MAX_INTERVAL, frequency).parse().unwrap();
+            assert!(rule.next_after(date("2026-10-19")).is_some());
+            assert_eq!(rule.next_after(NaiveDate::MAX), None, "{}", frequency);
+        }
+        let weekly = Recurrence { interval: MAX_INTERVAL, ..Recurrence::new(Frequency::Weekly) };
+        let weekly = Recurrence { weekdays: vec![Weekday::Mon], ..weekly };
+        assert_eq!(weekly.next_after(NaiveDate::MAX), None);
+    }
+}
@@ -0,0 +1,504 @@
+//! Statistics over the journal for `report`: tasks created and completed per
+//! period, how long tasks take to complete, how old the open ones are, and the
+//! same per tag and project.
+//!
+//! Completed tasks are kept until purged, so purged tasks no longer count.
+//! Periods follow the local calendar, with weeks starting on Monday.
+
+use crate::output::{self, OutputFormat};
+use crate::storage::Storage;
+use crate::table::{self, Colour, Table, Terminal};
+use crate::tasks::{Journal, Task};
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::Result;
+use std::str::FromStr;
+
+/// The levels of an ASCII sparkline, lowest first.
+const SPARKS: &[u8] = b".:-=+*#%@";
+
+/// The widest a bar gets, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// What the created and completed counts are grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The first day of the period `date` is in.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).expect("every month has a first day"),
+        }
+    }
+
+    /// The start of the period `count` periods before the one starting at `start`.
+    fn back(self, start: NaiveDate, count: u32) -> NaiveDate {
+        match self {
+            Period::Day => start - Days::new(count.into()),
+            Period::Week => start - Days::new(u64::from(count) * 7),
+            Period::Month => start - Months::new(count),
+        }
+    }
+
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => start.format("%G-W%V").to_string(),
+            Period::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "day" | "daily" => Ok(Period::Day),
+            "week" | "weekly" => Ok(Period::Week),
+            "month" | "monthly" => Ok(Period::Month),
+            _ => Err(format!("Unknown period '{}', expected day, week or month", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    period: Period,
+    since: NaiveDate,
+    periods: Vec<PeriodCounts>,
+    completion: CompletionTime,
+    backlog: Vec<AgeBucket>,
+    tags: Vec<Breakdown>,
+    projects: Vec<Breakdown>,
+}
+
+#[derive(Debug, Serialize)]
+struct PeriodCounts {
+    label: String,
+    start: NaiveDate,
+    created: usize,
+    completed: usize,
+}
+
+/// How long tasks completed in the report's periods took, in days.
+#[derive(Debug, Default, Serialize)]
+struct CompletionTime {
+    tasks: usize,
+    average_days: Option<f64>,
+    median_days: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct AgeBucket {
+    age: &'static str,
+    tasks: usize,
+}
+
+/// The numbers for one tag or project. `created` and `completed` count the
+/// report's periods; `open` counts every open task.
+#[derive(Debug, Default, Serialize)]
+struct Breakdown {
+    name: String,
+    open: usize,
+    created: usize,
+    completed: usize,
+    average_days: Option<f64>,
+}
+
+/// The open-task age buckets: a label and the age in days it goes up to.
+const AGES: [(&str, i64); 5] = [
+    ("< 1 week", 7),
+    ("1-4 weeks", 28),
+    ("1-3 months", 91),
+    ("3-12 months", 365),
+    ("> 1 year", i64::MAX),
+];
+
+/// Print the report for the last `count` periods, up to and including this one.
+pub fn print_report(storage: &mut dyn Storage, period: Period, count: u32, format: OutputFormat) -> Result<()> {
+    let journal = storage.load()?;
+    let report = build(&journal, period, count, Local::now().date_naive());
+    print!("{}", render(&report, format, Terminal::stdout()));
+    Ok(())
+}
+
+fn build(journal: &Journal, period: Period, count: u32, today: NaiveDate) -> Report {
+    let current = period.start(today);
+    let since = period.back(current, count.saturating_sub(1));
+    let mut periods: Vec<PeriodCounts> = (0..count)
+        .rev()
+        .map(|back| {
+            let start = period.back(current, back);
+            PeriodCounts {
+                label: period.label(start),
+                start,
+                created: 0,
+                completed: 0,
+            }
+        })
+        .collect();
+    let slot = |date: NaiveDate| -> Option<usize> {
+        let start = period.start(date);
+        periods.binary_search_by_key(&start, |counts| counts.start).ok()
+    };
+    let created_slots: Vec<Option<usize>> = journal.tasks.iter().map(|task| slot(created_on(task))).collect();
+    let completed_slots: Vec<Option<usize>> =
+        journal.tasks.iter().map(|task| completed_on(task).and_then(slot)).collect();
+
+    let mut durations = Vec::new();
+    let mut backlog = vec![0; AGES.len()];
+    let mut tags: BTreeMap<String, (Breakdown, Vec<f64>)> = BTreeMap::new();
+    let mut projects: BTreeMap<String, (Breakdown, Vec<f64>)> = BTreeMap::new();
+    for ((task, created), completed) in journal.tasks.iter().zip(created_slots).zip(completed_slots) {
+        if let Some(index) = created {
+            periods[index].created += 1;
+        }
+        let duration = completed.and_then(|index| {
+            periods[index].completed += 1;
+            let completed_at = task.completed_at?;
+            Some((completed_at - task.created_at).num_seconds().max(0) as f64 / 86_400.0)
+        });
+        durations.extend(duration);
+        if !task.is_completed() {
+            let age = (today - created_on(task)).num_days();
+            let bucket = AGES.iter().position(|&(_, up_to)| age < up_to).unwrap_or(AGES.len() - 1);
+            backlog[bucket] += 1;
+        }
+
+        let project = task.project.clone().unwrap_or_else(|| "(none)".to_string());
+        let groups = task.tags.iter().map(|tag| (true, format!("+{}", tag)));
+        for (is_tag, name) in groups.chain(std::iter::once((false, project))) {
+            let map = if is_tag { &mut tags } else { &mut projects };
+            let (breakdown, durations) = map.entry(name.clone()).or_insert_with(|| {
+                (
+                    Breakdown {
+                        name,
+                        ..Breakdown::default()
+                    },
+                    Vec::new(),
+                )
+            });
+            breakdown.open += usize::from(!task.is_completed());
+            breakdown.created += usize::from(created.is_some());
+            breakdown.completed += usize::from(completed.is_some());
+            durations.extend(duration);
+        }
+    }
+
+    let breakdowns = |map: BTreeMap<String, (Breakdown, Vec<f64>)>| -> Vec<Breakdown> {
+        map.into_values()
+            .map(|(breakdown, durations)| Breakdown {
+                average_days: average(&durations),
+                ..breakdown
+            })
+            .collect()
+    };
+    Report {
+        period,
+        since,
+        periods,
+        completion: CompletionTime {
+            tasks: durations.len(),
+            average_days: average(&durations),
+            median_days: median(&mut durations),
+        },
+        backlog: AGES
+            .iter()
+            .zip(backlog)
+            .map(|(&(age, _), tasks)| AgeBucket { age, tasks })
+            .collect(),
+        tags: breakdowns(tags),
+        projects: breakdowns(projects),
+    }
+}
+
+fn created_on(task: &Task) -> NaiveDate {
+    task.created_at.with_timezone(&Local).date_naive()
+}
+
+fn completed_on(task: &Task) -> Option<NaiveDate> {
+    task.completed_at.map(|at| at.with_timezone(&Local).date_naive())
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| round(values.iter().sum::<f64>() / values.len() as f64))
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    let median = match values.len() % 2 {
+        0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
+    };
+    Some(round(median))
+}
+
+fn round(days: f64) -> f64 {
+    (days * 100.0).round() / 100.0
+}
+
+fn render(report: &Report, format: OutputFormat, terminal: Terminal) -> String {
+    match format {
+        OutputFormat::Json => {
+            let mut out = serde_json::to_string_pretty(report).unwrap_or_default();
+            out.push('\n');
+            out
+        }
+        OutputFormat::Jsonl => rows(report)
+            .into_iter()
+            .map(|(section, key, metric, value)| {
+                json!({ "section": section, "key": key, "metric": metric, "value": value }).to_string() + "\n"
+            })
+            .collect(),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let header = ["section", "key", "metric", "value"].map(String::from);
+            let mut out = output::delimited_line(format, &header);
+            for (section, key, metric, value) in rows(report) {
+                let value = match value {
+                    Value::Null => String::new(),
+                    value => value.to_string(),
+                };
+                out.push_str(&output::delimited_line(format, &[section.to_string(), key, metric.to_string(), value]));
+            }
+            out
+        }
+        OutputFormat::Text | OutputFormat::Table => render_text(report, terminal),
+    }
+}
+
+/// The report as flat `(section, key, metric, value)` rows, for spreadsheets.
+fn rows(report: &Report) -> Vec<(&'static str, String, &'static str, Value)> {
+    let mut rows = Vec::new();
+    for counts in &report.periods {
+        rows.push((report.period.name(), counts.label.clone(), "created", json!(counts.created)));
+        rows.push((report.period.name(), counts.label.clone(), "completed", json!(counts.completed)));
+    }
+    let completion = &report.completion;
+    rows.push(("completion", "all".to_string(), "tasks", json!(completion.tasks)));
+    rows.push(("completion", "all".to_string(), "average_days", json!(completion.average_days)));
+    rows.push(("completion", "all".to_string(), "median_days", json!(completion.median_days)));
+    for bucket in &report.backlog {
+        rows.push(("backlog", bucket.age.to_string(), "open", json!(bucket.tasks)));
+    }
+    for (section, breakdowns) in [("tag", &report.tags), ("project", &report.projects)] {
+        for breakdown in breakdowns {
+            rows.push((section, breakdown.name.clone(), "open", json!(breakdown.open)));
+            rows.push((section, breakdown.name.clone(), "created", json!(breakdown.created)));
+            rows.push((section, breakdown.name.clone(), "completed", json!(breakdown.completed)));
+            rows.push((section, breakdown.name.clone(), "average_days", json!(breakdown.average_days)));
+        }
+    }
+    rows
+}
+
+fn render_text(report: &Report, terminal: Terminal) -> String {
+    let heading = |text: String| -> String {
+        if terminal.colour {
+            table::paint(&text, Colour::Bold) + "\n"
+        } else {
+            text + "\n"
+        }
+    };
+    let days = |days: Option<f64>| days.map(|days| format!("{:.1}", days)).unwrap_or_else(|| "-".to_string());
+    let mut out = String::new();
+
+    let first = report.periods.first().map(|counts| counts.label.as_str()).unwrap_or_default();
+    let last = report.periods.last().map(|counts| counts.label.as_str()).unwrap_or_default();
+    out += &heading(format!("Created and completed by {}, {} to {}", report.period.name(), first, last));
+    let created: Vec<usize> = report.periods.iter().map(|counts| counts.created).collect();
+    let completed: Vec<usize> = report.periods.iter().map(|counts| counts.completed).collect();
+    let most = created.iter().chain(&completed).copied().max().unwrap_or(0);
+    writeln!(out, "  created    {}  {}", sparkline(&created, most), created.iter().sum::<usize>()).unwrap();
+    writeln!(out, "  completed  {}  {}", sparkline(&completed, most), completed.iter().sum::<usize>()).unwrap();
+    out.push('\n');
+    let mut periods = Table::new([capitalise(report.period.name()), "Created".into(), "Completed".into(), "Net".into()]);
+    for counts in &report.periods {
+        let net = counts.created as i64 - counts.completed as i64;
+        periods.push(
+            vec![
+                counts.label.clone().into(),
+                counts.created.to_string().into(),
+                counts.completed.to_string().into(),
+                format!("{:+}", net).into(),
+            ],
+            None,
+        );
+    }
+    out += &periods.render(terminal);
+
+    out.push('\n');
+    out += &heading("Time to complete".to_string());
+    let completion = &report.completion;
+    writeln!(
+        out,
+        "  {} completed, taking {} days on average (median {})",
+        completion.tasks,
+        days(completion.average_days),
+        days(completion.median_days)
+    )
+    .unwrap();
+
+    out.push('\n');
+    out += &heading("Age of open tasks".to_string());
+    let most = report.backlog.iter().map(|bucket| bucket.tasks).max().unwrap_or(0);
+    let mut backlog = Table::new(["Age", "Tasks", ""]);
+    for bucket in &report.backlog {
+        backlog.push(
+            vec![bucket.age.into(), bucket.tasks.to_string().into(), bar(bucket.tasks, most).into()],
+            None,
+        );
+    }
+    out += &backlog.render(terminal);
+
+    for (title, breakdowns) in [("Tag", &report.tags), ("Project", &report.projects)] {
+        if breakdowns.is_empty() {
+            continue;
+        }
+        out.push('\n');
+        let mut table = Table::new([title, "Open", "Created", "Completed", "Avg days"]);
+        for breakdown in breakdowns {
+            table.push(
+                vec![
+                    breakdown.name.clone().into(),
+                    breakdown.open.to_string().into(),
+                    breakdown.created.to_string().into(),
+                    breakdown.completed.to_string().into(),
+                    days(breakdown.average_days).into(),
+                ],
+                None,
+            );
+        }
+        out += &table.render(terminal);
+    }
+    out
+}
+
+/// One character per value, taller for bigger values, scaled to `most`.
+fn sparkline(values: &[usize], most: usize) -> String {
+    values
+        .iter()
+        .map(|&value| match (value, most) {
+            (0, _) | (_, 0) => ' ',
+            _ => {
+                let level = (value * SPARKS.len()).div_ceil(most) - 1;
+                SPARKS[level.min(SPARKS.len() - 1)] as char
+            }
+        })
+        .collect()
+}
+
+fn bar(value: usize, most: usize) -> String {
+    if most == 0 {
+        return String::new();
+    }
+    "#".repeat((value * BAR_WIDTH).div_ceil(most))
+}
+
+fn capitalise(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn task(text: &str, created: &str, completed: Option<&str>) -> Task {
+        let at = |date: &str| {
+            let date: NaiveDate = date.parse().unwrap();
+            Local
+                .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let mut task = Task::new(text.to_string());
+        task.created_at = at(created);
+        if let Some(completed) = completed {
+            task.complete();
+            task.completed_at = Some(at(completed));
+        }
+        task
+    }
+
+    fn journal() -> Journal {
+        let mut journal = Journal::default();
+        let mut deploy = task("deploy", "2026-10-05", Some("2026-10-07"));
+        deploy.tags.insert("release".to_string());
+        deploy.project = Some("infra".to_string());
+        journal.push(deploy);
+        journal.push(task("write docs", "2026-10-13", Some("2026-10-14")));
+        journal.push(task("fix bug", "2026-10-16", None));
+        journal.push(task("old idea", "2025-01-01", None));
+        journal
+    }
+
+    #[test]
+    fn counts_fall_in_their_periods() {
+        // A Sunday, so this week started on the 12th.
+        let report = build(&journal(), Period::Week, 3, "2026-10-18".parse().unwrap());
+        let counts: Vec<(&str, usize, usize)> = report
+            .periods
+            .iter()
+            .map(|counts| (counts.label.as_str(), counts.created, counts.completed))
+            .collect();
+        assert_eq!(counts, [("2026-W40", 0, 0), ("2026-W41", 1, 1), ("2026-W42", 2, 1)]);
+        assert_eq!(report.completion.tasks, 2);
+        assert_eq!(report.completion.average_days, Some(1.5));
+        let backlog: Vec<usize> = report.backlog.iter().map(|bucket| bucket.tasks).collect();
+        assert_eq!(backlog, [1, 0, 0, 0, 1]);
+        assert_eq!(report.tags[0].name, "+release");
+        assert_eq!(report.tags[0].average_days, Some(2.0));
+        assert_eq!(report.projects.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), ["(none)", "infra"]);
+    }
+
+    #[test]
+    fn csv_rows_are_flat() {
+        let report = build(&journal(), Period::Month, 1, "2026-10-18".parse().unwrap());
+        let csv = render(&report, OutputFormat::Csv, Terminal::plain());
+        let lines: Vec<&str> = csv.lines().take(4).collect();
+        assert_eq!(
+            lines,
+            [
+                "section,key,metric,value",
+                "month,2026-10,created,3",
+                "month,2026-10,completed,2",
+                "completion,all,tasks,2"
+            ]
+        );
+    }
+
+    #[test]
+    fn sparklines_and_bars_scale() {
+        assert_eq!(sparkline(&[0, 1, 4, 8], 8), " :+@");
+        assert_eq!(bar(5, 10), "#".repeat(15));
+        assert_eq!(bar(0, 0), "");
+    }
+}
@@ -0,0 +1,316 @@
+//! Ranked, typo-tolerant search over task text, projects and tags.
+//!
+//! Text is folded before it is compared, so case and accents don't matter:
+//! `reuniao` finds "Reunião". Each search term has to match some word of a
+//! task, exactly, as a prefix, inside it, or with a typo or two for longer
+//! terms. Completed tasks are searched too, but rank a little lower.
+
+use crate::config;
+use crate::output::{self, Columns, OutputFormat};
+use crate::storage::Storage;
+use crate::table::{self, Colour, Terminal};
+use crate::tasks::Task;
+use chrono::Local;
+use std::cmp::Ordering;
+use std::io::Result;
+use std::ops::Range;
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+/// A task that matched, with the parts of its line that did.
+#[derive(Debug)]
+struct Hit<'a> {
+    task: &'a Task,
+    score: f64,
+    line: String,
+    /// Byte ranges of `line`, in order and not overlapping.
+    highlights: Vec<Range<usize>>,
+}
+
+/// Print the tasks best matching `terms`, at most `limit` of them.
+pub fn search_tasks(
+    storage: &mut dyn Storage,
+    terms: &[String],
+    limit: usize,
+    format: OutputFormat,
+    columns: &Columns,
+) -> Result<()> {
+    let journal = storage.load()?;
+    let terms = fold_words(&terms.join(" "));
+    let mut hits = search(&journal.tasks, &terms);
+    hits.truncate(limit);
+
+    if format != OutputFormat::Text {
+        let tasks: Vec<&Task> = hits.iter().map(|hit| hit.task).collect();
+        output::print_tasks(&journal, &tasks, format, columns);
+        return Ok(());
+    }
+    if hits.is_empty() {
+        println!("No tasks match.");
+        return Ok(());
+    }
+
+    let terminal = Terminal::stdout();
+    let id_width = hits.iter().map(|hit| hit.task.id.to_string().len()).max().unwrap_or(1);
+    for hit in &hits {
+        let mut line = hit.line.as_str();
+        if let Some(width) = terminal.width {
+            // Cut the line to the terminal, keeping only highlights that still show.
+            let cut = table::truncate(line, width.saturating_sub(id_width + 2));
+            line = &line[..cut.trim_end_matches('…').len().min(line.len())];
+        }
+        let mut shown = String::new();
+        let mut end = 0;
+        for range in &hit.highlights {
+            if range.end > line.len() {
+                break;
+            }
+            shown.push_str(&line[end..range.start]);
+            shown.push_str(&paint(&line[range.clone()], Colour::Highlight, terminal.colour));
+            end = range.end;
+        }
+        shown.push_str(&line[end..]);
+        if line.len() < hit.line.len() {
+            shown.push('…');
+        }
+        if let Some(completed_at) = hit.task.completed_at {
+            let completed_on = completed_at.with_timezone(&Local).format(&config::appearance().date_format);
+            let done = format!(" (done {})", completed_on);
+            shown.push_str(&paint(&done, Colour::Dim, terminal.colour));
+        }
+        println!("{:>width$}: {}", hit.task.id, shown, width = id_width);
+    }
+    Ok(())
+}
+
+fn paint(text: &str, colour: Colour, use_colour: bool) -> String {
+    if use_colour {
+        table::paint(text, colour)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Every task matching all of `terms`, best first.
+fn search<'a>(tasks: &'a [Task], terms: &[FoldedWord]) -> Vec<Hit<'a>> {
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<Hit> = tasks.iter().filter_map(|task| score(task, terms)).collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then(b.task.id.cmp(&a.task.id))
+    });
+    hits
+}
+
+fn score<'a>(task: &'a Task, terms: &[FoldedWord]) -> Option<Hit<'a>> {
+    // The line shown for a task is also what is searched.
+    let mut line = task.text.clone();
+    if let Some(project) = &task.project {
+        line.push_str(" project:");
+        line.push_str(project);
+    }
+    for tag in &task.tags {
+        line.push_str(" +");
+        line.push_str(tag);
+    }
+
+    let words = fold_words(&line);
+    let mut score = 0.0;
+    let mut highlights = Vec::new();
+    for term in terms {
+        let (best, range) = words
+            .iter()
+            .filter_map(|word| match_word(term, word))
+            .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))?;
+        score += best;
+        highlights.push(range);
+    }
+    if task.is_completed() {
+        score *= 0.8;
+    }
+
+    highlights.sort_by_key(|range| range.start);
+    highlights.dedup_by(|next, previous| {
+        // Merge overlapping ranges into the earlier one.
+        if next.start < previous.end {
+            previous.end = previous.end.max(next.end);
+            true
+        } else {
+            false
+        }
+    });
+    Some(Hit {
+        task,
+        score,
+        line,
+        highlights,
+    })
+}
+
+/// How well `term` matches `word`, and which bytes of the original text matched.
+fn match_word(term: &FoldedWord, word: &FoldedWord) -> Option<(f64, Range<usize>)> {
+    let (term, letters) = (&term.chars, &word.chars);
+    if letters == term {
+        return Some((3.0, word.range(0..letters.len())));
+    }
+    if letters.starts_with(term) {
+        return Some((2.0, word.range(0..term.len())));
+    }
+    if term.len() >= 3 {
+        if let Some(start) = letters.windows(term.len()).position(|window| window == term.as_slice()) {
+            return Some((1.5, word.range(start..start + term.len())));
+        }
+    }
+    // Longer terms may have typos.
+    let typos = match term.len() {
+        0..=3 => return None,
+        4..=7 => 1,
+        _ => 2,
+    };
+    let whole = distance(term, letters, typos).map(|distance| (distance, letters.len()));
+    // Also look for a typo in a prefix, for terms typed before finishing a word.
+    let prefix = letters
+        .get(..term.len())
+        .filter(|_| letters.len() > term.len())
+        .and_then(|prefix| distance(term, prefix, typos))
+        .map(|distance| (distance + 1, term.len()));
+    let (distance, end) = whole.into_iter().chain(prefix).min()?;
+    Some((1.0 / (1.0 + distance as f64), word.range(0..end)))
+}
+
+/// The optimal string alignment distance between `a` and `b`, or `None` when
+/// it is more than `max`.
+fn distance(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        if current.iter().min().is_some_and(|&min| min > max) {
+            return None;
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+/// A word with case and accents folded away, remembering where each of its
+/// letters came from.
+#[derive(Debug)]
+struct FoldedWord {
+    chars: Vec<char>,
+    /// The byte range in the original text of each of `chars`.
+    sources: Vec<Range<usize>>,
+}
+
+impl FoldedWord {
+    /// The original bytes behind `letters`, a range of `chars`.
+    fn range(&self, letters: Range<usize>) -> Range<usize> {
+        self.sources[letters.start].start..self.sources[letters.end - 1].end
+    }
+}
+
+fn fold_words(text: &str) -> Vec<FoldedWord> {
+    let mut words = Vec::new();
+    let mut word = FoldedWord {
+        chars: Vec::new(),
+        sources: Vec::new(),
+    };
+    for (start, c) in text.char_indices() {
+        let source = start..start + c.len_utf8();
+        if !c.is_alphanumeric() {
+            if !word.chars.is_empty() {
+                words.push(std::mem::replace(
+                    &mut word,
+                    FoldedWord {
+                        chars: Vec::new(),
+                        sources: Vec::new(),
+                    },
+                ));
+            }
+            continue;
+        }
+        let mut base = Vec::new();
+        decompose_canonical(c, |part| {
+            if !is_combining_mark(part) {
+                base.push(part)
+            }
+        });
+        for folded in base.into_iter().flat_map(char::to_lowercase) {
+            word.chars.push(folded);
+            word.sources.push(source.clone());
+        }
+    }
+    if !word.chars.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::Journal;
+
+    fn journal(texts: &[&str]) -> Journal {
+        let mut journal = Journal::default();
+        for text in texts {
+            journal.push(Task::new(text.to_string()));
+        }
+        journal
+    }
+
+    fn ids(journal: &Journal, query: &str) -> Vec<u64> {
+        search(&journal.tasks, &fold_words(query)).iter().map(|hit| hit.task.id).collect()
+    }
+
+    #[test]
+    fn accents_case_and_typos_are_forgiven() {
+        let mut journal = journal(&[
+            "Reunião com a equipe",
+            "Preparar apresentação",
+            "Call the accountant",
+            "Account for the reunion",
+        ]);
+        journal.tasks[2].tags.insert("finanças".to_string());
+        journal.tasks[3].status = crate::tasks::Status::Completed;
+
+        assert_eq!(ids(&journal, "reuniao"), [1]);
+        assert_eq!(ids(&journal, "APRESENTACAO"), [2]);
+        assert_eq!(ids(&journal, "acountant"), [3]);
+        assert_eq!(ids(&journal, "financas"), [3]);
+        // Better matches first, and every term has to match.
+        assert_eq!(ids(&journal, "account"), [4, 3]);
+        assert_eq!(ids(&journal, "call account"), [3]);
+        assert!(ids(&journal, "xyz").is_empty());
+    }
+
+    #[test]
+    fn highlights_point_at_the_original_text() {
+        let journal = journal(&["Revisar o orçamento de 2026"]);
+        let hits = search(&journal.tasks, &fold_words("orcamento revis"));
+        let hit = &hits[0];
+        let highlighted: Vec<&str> = hit.highlights.iter().map(|range| &hit.line[range.clone()]).collect();
+        assert_eq!(highlighted, ["Revis", "orçamento"]);
+    }
+
+    #[test]
+    fn typos_are_bounded() {
+        let word = |text: &str| text.chars().collect::<Vec<_>>();
+        assert_eq!(distance(&word("recieve"), &word("receive"), 1), Some(1));
+        assert_eq!(distance(&word("kitten"), &word("sitting"), 2), None);
+        assert_eq!(distance(&word("kitten"), &word("sitting"), 3), Some(3));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::history::History;
+use crate::migrations::CURRENT_VERSION;
+use crate::tasks::Journal;
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+mod json;
+#[cfg(test)]
+mod memory;
+mod sqlite;
+
+pub use json::JsonFile;
+#[cfg(test)]
+pub use memory::MemoryStore;
+pub use sqlite::SqliteDatabase;
+
+/// Somewhere a journal can be loaded from and saved to.
+///
+/// Every task command goes through this trait, so they work the same no matter
+/// where the journal lives.
+pub trait Storage {
+    /// Read the whole journal. A journal that doesn't exist yet is empty.
+    fn load(&mut self) -> Result<Journal>;
+
+    /// Replace the stored journal with `journal`.
+    fn save(&mut self, journal: &Journal) -> Result<()>;
+
+    /// Read the operation log used by `undo` and `redo`.
+    fn load_history(&mut self) -> Result<History>;
+
+    /// Replace the stored operation log.
+    fn save_history(&mut self, history: &History) -> Result<()>;
+
+    /// Replace both the journal and the operation log, for `undo` and `redo`,
+    /// which mustn't leave one changed without the other. If the log can't be
+    /// saved, the journal is put back the way it was.
+    fn save_with_history(&mut self, journal: &Journal, history: &History) -> Result<()> {
+        let previous = self.load()?;
+        self.save(journal)?;
+        if let Err(e) = self.save_history(history) {
+            let _ = self.save(&previous);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// The storage backends that can be picked on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Json,
+    Sqlite,
+}
+
+impl Backend {
+    /// Guess the backend from the journal's file extension.
+    pub fn from_path(path: &Path) -> Backend {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            _ => Backend::Json,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Backend::Json),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(format!("Unknown backend '{}', expected json or sqlite", s)),
+        }
+    }
+}
+
+/// Open the journal at `path`, with the backend given or the one its extension suggests.
+pub fn open(path: &Path, backend: Option<Backend>) -> Result<Box<dyn Storage>> {
+    let storage: Box<dyn Storage> = match backend.unwrap_or_else(|| Backend::from_path(path)) {
+        Backend::Json => Box::new(JsonFile::new(path)),
+        Backend::Sqlite => Box::new(SqliteDatabase::open(path)?),
+    };
+    Ok(storage)
+}
+
+/// Refuse to save a journal that a newer rusty-journal wrote, since we'd
+/// silently drop whatever it stored that we don't know about.
+fn check_writable(journal: &Journal) -> Result<()> {
+    if journal.version > CURRENT_VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The journal uses format version {}, but this rusty-journal only supports up to {}. \
+                 Refusing to overwrite it; please upgrade rusty-journal.",
+                journal.version, CURRENT_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Replace a file without ever leaving a partly written one behind.
+///
+/// `write` writes the new contents to a temporary file next to it, which is
+/// synced to disk and then renamed over the original. A rename within a
+/// directory is atomic, so after a crash the file holds either the old or the
+/// new contents. The temporary file is named after the process, so two
+/// processes writing at once can't write into the same one.
+pub fn write_atomically(path: &Path, write: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let written = File::create(&temp_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    fs::rename(&temp_path, path)?;
+
+    // Sync the directory too, so the rename itself survives a crash.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
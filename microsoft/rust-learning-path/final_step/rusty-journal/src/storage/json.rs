@@ -0,0 +1,73 @@
+use super::{check_writable, write_atomically, Storage};
+use crate::history::History;
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::tasks::Journal;
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+/// A journal kept as a single JSON file, rewritten in full on every save.
+///
+/// The operation log lives next to it in `<journal>.history.json`.
+pub struct JsonFile {
+    path: PathBuf,
+}
+
+impl JsonFile {
+    pub fn new(path: &Path) -> JsonFile {
+        JsonFile { path: path.to_path_buf() }
+    }
+
+    fn history_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".history.json");
+        self.path.with_file_name(name)
+    }
+}
+
+impl Storage for JsonFile {
+    /// Read the journal, upgrading older layouts. A missing or empty file is an
+    /// empty journal.
+    fn load(&mut self) -> Result<Journal> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Journal::default()),
+            Err(e) => return Err(e),
+        };
+        let value: serde_json::Value = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(value) => value,
+            Err(e) if e.is_eof() => return Ok(Journal::default()),
+            Err(e) => Err(e)?,
+        };
+
+        let version = migrations::detect_version(&value)?;
+        let journal: Journal = serde_json::from_value(migrations::upgrade(value)?)?;
+
+        // Save upgraded journals right away, keeping the old file as a backup, so
+        // the IDs handed out during the upgrade stay the same from now on.
+        if version < CURRENT_VERSION {
+            let mut backup_name = self.path.file_name().unwrap_or_default().to_os_string();
+            backup_name.push(format!(".v{}.bak", version));
+            fs::copy(&self.path, self.path.with_file_name(backup_name))?;
+            self.save(&journal)?;
+        }
+        Ok(journal)
+    }
+
+    fn save(&mut self, journal: &Journal) -> Result<()> {
+        check_writable(journal)?;
+        write_atomically(&self.path, |file| Ok(serde_json::to_writer(file, journal)?))
+    }
+
+    fn load_history(&mut self) -> Result<History> {
+        match File::open(self.history_path()) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(History::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save_history(&mut self, history: &History) -> Result<()> {
+        write_atomically(&self.history_path(), |file| Ok(serde_json::to_writer(file, history)?))
+    }
+}
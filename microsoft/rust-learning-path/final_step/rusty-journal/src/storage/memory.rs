@@ -0,0 +1,32 @@
+use super::{check_writable, Storage};
+use crate::history::History;
+use crate::tasks::Journal;
+use std::io::Result;
+
+/// A journal that only lives as long as the value does. Handy for tests.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    journal: Journal,
+    history: History,
+}
+
+impl Storage for MemoryStore {
+    fn load(&mut self) -> Result<Journal> {
+        Ok(self.journal.clone())
+    }
+
+    fn save(&mut self, journal: &Journal) -> Result<()> {
+        check_writable(journal)?;
+        self.journal = journal.clone();
+        Ok(())
+    }
+
+    fn load_history(&mut self) -> Result<History> {
+        Ok(self.history.clone())
+    }
+
+    fn save_history(&mut self, history: &History) -> Result<()> {
+        self.history = history.clone();
+        Ok(())
+    }
+}
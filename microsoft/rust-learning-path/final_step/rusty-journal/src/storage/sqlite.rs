@@ -0,0 +1,196 @@
+use super::{check_writable, Storage};
+use crate::history::History;
+use crate::migrations::CURRENT_VERSION;
+use crate::tasks::{Journal, Task};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::path::Path;
+
+/// A journal kept in an SQLite database.
+///
+/// Each task is a row holding its JSON form, so new `Task` fields need no schema
+/// change. Saving only touches the rows that changed since the last load, which
+/// keeps big journals fast.
+pub struct SqliteDatabase {
+    connection: Connection,
+    /// What each row held after the last load or save: its position and JSON.
+    /// `None` until then, in which case a save rewrites every row.
+    stored: Option<HashMap<u64, (usize, String)>>,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key   TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tasks (
+        id       INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data     TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tasks_position ON tasks (position);
+";
+
+impl SqliteDatabase {
+    pub fn open(path: &Path) -> Result<SqliteDatabase> {
+        let connection = Connection::open(path).map_err(Error::other)?;
+        connection.execute_batch(SCHEMA).map_err(Error::other)?;
+        Ok(SqliteDatabase { connection, stored: None })
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        self.connection
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .map_err(Error::other)
+    }
+}
+
+impl Storage for SqliteDatabase {
+    fn load(&mut self) -> Result<Journal> {
+        let version = match self.meta("version")? {
+            Some(version) => version.parse().map_err(Error::other)?,
+            None => CURRENT_VERSION,
+        };
+        let next_id = match self.meta("next_id")? {
+            Some(next_id) => next_id.parse().map_err(Error::other)?,
+            None => 1,
+        };
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM tasks ORDER BY position")
+            .map_err(Error::other)?;
+        let rows = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(Error::other)?;
+
+        let mut tasks = Vec::new();
+        let mut stored = HashMap::new();
+        for (position, data) in rows.enumerate() {
+            let data = data.map_err(Error::other)?;
+            let task: Task = serde_json::from_str(&data)?;
+            stored.insert(task.id, (position, data));
+            tasks.push(task);
+        }
+
+        self.stored = Some(stored);
+        Ok(Journal { version, next_id, tasks })
+    }
+
+    fn save(&mut self, journal: &Journal) -> Result<()> {
+        check_writable(journal)?;
+        let transaction = self.connection.transaction().map_err(Error::other)?;
+        let stored = write_journal(&transaction, self.stored.take(), journal)?;
+        transaction.commit().map_err(Error::other)?;
+        self.stored = Some(stored);
+        Ok(())
+    }
+
+    fn load_history(&mut self) -> Result<History> {
+        match self.meta("history")? {
+            Some(history) => Ok(serde_json::from_str(&history)?),
+            None => Ok(History::default()),
+        }
+    }
+
+    fn save_history(&mut self, history: &History) -> Result<()> {
+        write_history(&self.connection, history)
+    }
+
+    /// Both in one transaction, so they're saved together or not at all.
+    fn save_with_history(&mut self, journal: &Journal, history: &History) -> Result<()> {
+        check_writable(journal)?;
+        let transaction = self.connection.transaction().map_err(Error::other)?;
+        let stored = write_journal(&transaction, self.stored.take(), journal)?;
+        write_history(&transaction, history)?;
+        transaction.commit().map_err(Error::other)?;
+        self.stored = Some(stored);
+        Ok(())
+    }
+}
+
+/// Write the rows that changed since `previous`, what the rows held after the
+/// last load or save, and return what they hold now. Without `previous`,
+/// every row is rewritten.
+fn write_journal(
+    transaction: &Transaction,
+    previous: Option<HashMap<u64, (usize, String)>>,
+    journal: &Journal,
+) -> Result<HashMap<u64, (usize, String)>> {
+    let mut stored = HashMap::with_capacity(journal.tasks.len());
+    // Without a snapshot to compare against, start from a clean table.
+    if previous.is_none() {
+        transaction.execute("DELETE FROM tasks", []).map_err(Error::other)?;
+    }
+    let previous = previous.unwrap_or_default();
+
+    let mut upsert = transaction
+        .prepare("INSERT OR REPLACE INTO tasks (id, position, data) VALUES (?1, ?2, ?3)")
+        .map_err(Error::other)?;
+    for (position, task) in journal.tasks.iter().enumerate() {
+        let data = serde_json::to_string(task)?;
+        let unchanged = previous
+            .get(&task.id)
+            .is_some_and(|(old_position, old_data)| *old_position == position && *old_data == data);
+        if !unchanged {
+            upsert
+                .execute(params![task.id as i64, position as i64, data])
+                .map_err(Error::other)?;
+        }
+        stored.insert(task.id, (position, data));
+    }
+
+    let mut delete = transaction
+        .prepare("DELETE FROM tasks WHERE id = ?1")
+        .map_err(Error::other)?;
+    for id in previous.keys().filter(|id| !stored.contains_key(id)) {
+        delete.execute([*id as i64]).map_err(Error::other)?;
+    }
+
+    transaction
+        .execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('version', ?1), ('next_id', ?2)",
+            params![CURRENT_VERSION.to_string(), journal.next_id.to_string()],
+        )
+        .map_err(Error::other)?;
+    Ok(stored)
+}
+
+fn write_history(connection: &Connection, history: &History) -> Result<()> {
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('history', ?1)",
+            [serde_json::to_string(history)?],
+        )
+        .map_err(Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_only_what_changed_and_round_trips() {
+        let mut database = SqliteDatabase::open(Path::new(":memory:")).unwrap();
+        let mut journal = database.load().unwrap();
+        for text in ["one", "two", "three"] {
+            journal.push(Task::new(text.to_string()));
+        }
+        database.save(&journal).unwrap();
+
+        let mut journal = database.load().unwrap();
+        journal.tasks.remove(1);
+        journal.tasks[1].complete();
+        database.save(&journal).unwrap();
+
+        let reloaded = database.load().unwrap();
+        assert_eq!(
+            serde_json::to_value(&reloaded).unwrap(),
+            serde_json::to_value(&journal).unwrap()
+        );
+        assert_eq!(reloaded.next_id, 4);
+    }
+}
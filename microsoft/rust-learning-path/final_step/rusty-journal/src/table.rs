@@ -0,0 +1,396 @@
+//! Tables for the terminal.
+//!
+//! Widths are measured in terminal columns per grapheme, so accents, CJK text
+//! and emoji line up. One column can be marked as flexible: it wraps when the
+//! table is wider than the terminal, and the others are cut short with `…`.
+
+use crate::config::{self, ColourChoice};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Space between columns.
+const GAP: &str = "  ";
+/// The narrowest a flexible column gets before other columns are cut.
+const MIN_FLEXIBLE: usize = 12;
+/// The narrowest any other column gets.
+const MIN_WIDTH: usize = 3;
+
+/// The colours a cell can be printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Magenta,
+    Cyan,
+    Dim,
+    Bold,
+    /// Bold and underlined, for the parts of text that matched a search.
+    Highlight,
+}
+
+impl Colour {
+    fn code(self) -> &'static str {
+        match self {
+            Colour::Red => "\x1b[31m",
+            Colour::Yellow => "\x1b[33m",
+            Colour::Green => "\x1b[32m",
+            Colour::Blue => "\x1b[34m",
+            Colour::Magenta => "\x1b[35m",
+            Colour::Cyan => "\x1b[36m",
+            Colour::Dim => "\x1b[2m",
+            Colour::Bold => "\x1b[1m",
+            Colour::Highlight => "\x1b[1;4m",
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "red" => Ok(Colour::Red),
+            "yellow" => Ok(Colour::Yellow),
+            "green" => Ok(Colour::Green),
+            "blue" => Ok(Colour::Blue),
+            "magenta" => Ok(Colour::Magenta),
+            "cyan" => Ok(Colour::Cyan),
+            "dim" => Ok(Colour::Dim),
+            "bold" => Ok(Colour::Bold),
+            _ => Err(format!(
+                "Unknown colour '{}', expected red, yellow, green, blue, magenta, cyan, dim, bold or none",
+                s
+            )),
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+
+/// `text` in `colour`, for a terminal.
+pub fn paint(text: &str, colour: Colour) -> String {
+    format!("{}{}{}", colour.code(), text, RESET)
+}
+
+/// Where a table is printed: how wide it may be and whether it may use colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Terminal {
+    pub width: Option<usize>,
+    pub colour: bool,
+}
+
+impl Terminal {
+    /// Stdout as it is. Output that isn't going to a terminal gets no colour
+    /// and no width limit, unless `$COLUMNS` sets one, and `$NO_COLOR` turns
+    /// colour off everywhere, unless the `colour` setting says otherwise.
+    pub fn stdout() -> Terminal {
+        let is_terminal = io::stdout().is_terminal();
+        let columns = env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok());
+        let width = match is_terminal {
+            true => columns.or_else(|| terminal_size::terminal_size().map(|(width, _)| width.0 as usize)),
+            false => columns,
+        };
+        let no_colour = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let colour = match config::appearance().colour {
+            ColourChoice::Always => true,
+            ColourChoice::Never => false,
+            ColourChoice::Auto => is_terminal && !no_colour,
+        };
+        Terminal {
+            width: width.filter(|&width| width > 0),
+            colour,
+        }
+    }
+}
+
+/// A table cell, optionally coloured.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub text: String,
+    pub colour: Option<Colour>,
+}
+
+impl Cell {
+    pub fn coloured(text: impl Into<String>, colour: Option<Colour>) -> Cell {
+        Cell {
+            text: text.into(),
+            colour,
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Cell {
+        Cell { text, colour: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Cell {
+        Cell::from(text.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    cells: Vec<Cell>,
+    /// Colours the whole row, for cells without a colour of their own.
+    colour: Option<Colour>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Row>,
+    flexible: Option<usize>,
+}
+
+impl Table {
+    pub fn new<S: Into<String>>(headers: impl IntoIterator<Item = S>) -> Table {
+        Table {
+            headers: headers.into_iter().map(Into::into).collect(),
+            rows: Vec::new(),
+            flexible: None,
+        }
+    }
+
+    /// Let the column at `index` wrap to make the table fit.
+    pub fn flexible(mut self, index: usize) -> Table {
+        self.flexible = Some(index);
+        self
+    }
+
+    /// Add a row. Missing cells are left blank.
+    pub fn push(&mut self, cells: Vec<Cell>, colour: Option<Colour>) {
+        self.rows.push(Row { cells, colour });
+    }
+
+    pub fn render(&self, terminal: Terminal) -> String {
+        let widths = self.fit(terminal.width);
+        let mut out = String::new();
+
+        let headers: Vec<Cell> = self.headers.iter().map(|header| Cell::from(header.as_str())).collect();
+        self.render_row(&mut out, &headers, Some(Colour::Bold), &widths, terminal.colour);
+        for row in &self.rows {
+            self.render_row(&mut out, &row.cells, row.colour, &widths, terminal.colour);
+        }
+        out
+    }
+
+    fn render_row(&self, out: &mut String, cells: &[Cell], colour: Option<Colour>, widths: &[usize], use_colour: bool) {
+        let empty = Cell::default();
+        // Every cell as the lines it takes up; only the flexible one wraps.
+        let lines: Vec<Vec<String>> = widths
+            .iter()
+            .enumerate()
+            .map(|(index, &width)| {
+                let text = &cells.get(index).unwrap_or(&empty).text;
+                if Some(index) == self.flexible {
+                    wrap(text, width)
+                } else {
+                    vec![truncate(text, width)]
+                }
+            })
+            .collect();
+        let height = lines.iter().map(Vec::len).max().unwrap_or(1);
+
+        for line in 0..height {
+            let mut printed = String::new();
+            for (index, &width) in widths.iter().enumerate() {
+                if index > 0 {
+                    printed.push_str(GAP);
+                }
+                let text = lines[index].get(line).map_or("", String::as_str);
+                let colour = cells.get(index).and_then(|cell| cell.colour).or(colour);
+                match colour {
+                    Some(colour) if use_colour && !text.is_empty() => {
+                        printed.push_str(&paint(text, colour));
+                        printed.push_str(&" ".repeat(width.saturating_sub(display_width(text))));
+                    }
+                    _ => printed.push_str(&pad(text, width)),
+                }
+            }
+            out.push_str(printed.trim_end());
+            out.push('\n');
+        }
+    }
+
+    /// The width of each column, narrowed until the table fits in `limit`.
+    fn fit(&self, limit: Option<usize>) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| display_width(header)).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(&row.cells) {
+                *width = (*width).max(display_width(&cell.text));
+            }
+        }
+        let Some(limit) = limit else {
+            return widths;
+        };
+
+        let gaps = GAP.len() * widths.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > limit {
+            // Wrap the flexible column first, then cut the widest other one.
+            let flexible = self.flexible.filter(|&index| widths[index] > MIN_FLEXIBLE);
+            let widest = (0..widths.len())
+                .filter(|&index| Some(index) != self.flexible && widths[index] > MIN_WIDTH)
+                .max_by_key(|&index| widths[index]);
+            match flexible.or(widest) {
+                Some(index) => widths[index] -= 1,
+                None => break,
+            }
+        }
+        widths
+    }
+}
+
+/// How many terminal columns `text` takes up.
+pub fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+fn grapheme_width(grapheme: &str) -> usize {
+    // An emoji presentation selector makes the grapheme wide.
+    if grapheme.contains('\u{fe0f}') {
+        return 2;
+    }
+    grapheme.width().min(2)
+}
+
+/// `text` padded with spaces to `width` columns.
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(display_width(text))))
+}
+
+/// `text` cut to at most `width` columns, ending in `…` if anything was cut.
+pub fn truncate(text: &str, width: usize) -> String {
+    if display_width(text) <= width {
+        return text.to_string();
+    }
+    let mut cut = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme_width(grapheme);
+        if used + grapheme_width + 1 > width {
+            break;
+        }
+        cut.push_str(grapheme);
+        used += grapheme_width;
+    }
+    cut.push('…');
+    cut
+}
+
+/// `text` broken into lines of at most `width` columns, at spaces where
+/// possible.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut used = 0;
+    for word in text.split_word_bounds() {
+        let word_width = display_width(word);
+        if used + word_width > width && used > 0 {
+            lines.push(line.trim_end().to_string());
+            line.clear();
+            used = 0;
+            if word.trim().is_empty() {
+                continue;
+            }
+        }
+        if word_width <= width {
+            line.push_str(word);
+            used += word_width;
+            continue;
+        }
+        // A word longer than the whole line is broken between graphemes.
+        for grapheme in word.graphemes(true) {
+            let grapheme_width = grapheme_width(grapheme);
+            if used + grapheme_width > width && used > 0 {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+            line.push_str(grapheme);
+            used += grapheme_width;
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Terminal {
+        pub fn plain() -> Terminal {
+            Terminal {
+                width: None,
+                colour: false,
+            }
+        }
+    }
+
+    #[test]
+    fn wide_text_lines_up() {
+        assert_eq!(display_width("Reunião"), 7);
+        assert_eq!(display_width("買い物"), 6);
+        assert_eq!(display_width("🎉 party"), 8);
+        assert_eq!(display_width("e\u{301}"), 1);
+
+        let mut table = Table::new(["ID", "Task", "Due"]);
+        table.push(vec!["1".into(), "買い物".into(), "2026-10-20".into()], None);
+        table.push(vec!["2".into(), "Reunião 🎉".into(), "".into()], None);
+        assert_eq!(
+            table.render(Terminal::plain()),
+            "ID  Task        Due\n1   買い物      2026-10-20\n2   Reunião 🎉\n"
+        );
+    }
+
+    #[test]
+    fn narrow_terminals_wrap_the_flexible_column() {
+        let mut table = Table::new(["ID", "Task", "Project"]).flexible(1);
+        table.push(
+            vec!["1".into(), "Write the quarterly report for the board".into(), "management".into()],
+            None,
+        );
+        let terminal = Terminal {
+            width: Some(30),
+            colour: false,
+        };
+        let rendered = table.render(terminal);
+        assert!(rendered.lines().all(|line| display_width(line) <= 30), "{}", rendered);
+        assert_eq!(
+            rendered,
+            "ID  Task            Project\n1   Write the       management\n    quarterly\n    report for the\n    board\n"
+        );
+
+        let terminal = Terminal {
+            width: Some(22),
+            colour: false,
+        };
+        assert!(table.render(terminal).contains("1   Write the     man…"));
+        assert_eq!(truncate("買い物リスト", 7), "買い物…");
+    }
+
+    #[test]
+    fn colour_is_only_used_when_allowed() {
+        let mut table = Table::new(["Priority"]);
+        table.push(vec![Cell::coloured("high", Some(Colour::Red))], None);
+        assert_eq!(table.render(Terminal::plain()), "Priority\nhigh\n");
+        let terminal = Terminal {
+            width: None,
+            colour: true,
+        };
+        assert_eq!(
+            table.render(terminal),
+            "\x1b[1mPriority\x1b[0m\n\x1b[31mhigh\x1b[0m\n"
+        );
+    }
+}
@@ -1,110 +1,1256 @@
-use chrono::{serde::ts_seconds, DateTime, Local, Utc};
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use crate::config;
+use crate::editor;
+use crate::migrations::CURRENT_VERSION;
+use crate::output::{self, Columns, OutputFormat};
+use crate::query::Query;
+use crate::recurrence::Recurrence;
+use crate::storage::Storage;
+use crate::table::{self, Cell, Colour, Table, Terminal};
+use crate::timesheet::TimeEntry;
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::Deserialize;
 use serde::Serialize;
-use std::fs::{File, OpenOptions};
-use std::path::PathBuf;
-use std::io::{Error, ErrorKind, Result, Seek, SeekFrom};  // Include the `Error` type.
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};  // Include the `Error` type.
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Task {
+    /// Persistent identifier, handed out by `Journal::push`.
+    pub id: u64,
+
+    /// The task's UID in calendar apps, for tasks imported from one. See `Task::uid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+
+    /// The task this one is a step of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u64>,
+
     pub text: String,
 
     #[serde(with = "ts_seconds")]
     pub created_at: DateTime<Utc>,
+
+    #[serde(default)]
+    pub status: Status,
+
+    #[serde(default, with = "ts_seconds_option", skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<NaiveDate>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+
+    /// The tasks that have to be done before this one can start.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub depends: BTreeSet<u64>,
+
+    /// Repeat the task: completing it adds the next occurrence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+
+    /// Extra `key:value` data from imported tasks that has no field of its
+    /// own, kept so it survives being exported again.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, String>,
+
+    /// Time spent on the task, see `timesheet`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time: Vec<TimeEntry>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Pending,
+    Completed,
+}
+
+/// Declared in ascending order so that `High > Medium > Low`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    /// How much the priority adds to a task's urgency.
+    fn weight(self) -> f64 {
+        match self {
+            Priority::High => 6.0,
+            Priority::Medium => 3.9,
+            Priority::Low => 1.8,
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "h" | "high" => Ok(Priority::High),
+            "m" | "medium" => Ok(Priority::Medium),
+            "l" | "low" => Ok(Priority::Low),
+            _ => Err(format!("Unknown priority '{}', expected high, medium or low", s)),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::High => "high",
+            Priority::Medium => "medium",
+            Priority::Low => "low",
+        };
+        f.write_str(name)
+    }
 }
 
 impl Task {
     pub fn new(text: String) -> Task {
         let created_at: DateTime<Utc> = Utc::now();
-        Task { text, created_at }
+        Task {
+            id: 0,
+            uid: None,
+            parent: None,
+            text,
+            created_at,
+            status: Status::Pending,
+            completed_at: None,
+            due: None,
+            priority: None,
+            tags: BTreeSet::new(),
+            project: None,
+            depends: BTreeSet::new(),
+            recurrence: None,
+            extensions: BTreeMap::new(),
+            time: Vec::new(),
+        }
     }
+
+    /// A globally unique ID for calendar apps: the imported one, or one made
+    /// from the task's ID and creation time.
+    pub fn uid(&self) -> String {
+        match &self.uid {
+            Some(uid) => uid.clone(),
+            None => format!("{}-{}@rusty-journal", self.id, self.created_at.timestamp()),
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.status == Status::Completed
+    }
+
+    /// Mark the task as done now, stopping its timer if it's running.
+    pub fn complete(&mut self) {
+        let now = Utc::now();
+        self.status = Status::Completed;
+        self.completed_at = Some(now);
+        for entry in self.time.iter_mut().filter(|entry| entry.end.is_none()) {
+            entry.end = Some(now.max(entry.start));
+        }
+    }
+
+    /// The task that follows this one if it recurs: a fresh copy due on the
+    /// rule's next date after `today`. Occurrences missed while the task was
+    /// overdue are skipped rather than piling up.
+    pub fn next_occurrence(&self, today: NaiveDate) -> Option<Task> {
+        let recurrence = self.recurrence.as_ref()?;
+        let mut due = recurrence.next_after(self.due.unwrap_or(today))?;
+        let mut rule = recurrence.for_next();
+        while due < today {
+            due = rule.next_after(due)?;
+            rule = rule.for_next();
+        }
+
+        let mut next = Task::new(self.text.clone());
+        next.due = Some(due);
+        next.priority = self.priority;
+        next.tags = self.tags.clone();
+        next.project = self.project.clone();
+        next.parent = self.parent;
+        next.extensions = self.extensions.clone();
+        next.recurrence = Some(rule);
+        Some(next)
+    }
+
+    /// The dependencies that are still open, given the IDs of all open tasks.
+    /// Dependencies on tasks that have been purged don't count.
+    pub fn open_dependencies<'a>(&'a self, open: &'a HashSet<u64>) -> impl Iterator<Item = u64> + 'a {
+        self.depends.iter().copied().filter(|id| open.contains(id))
+    }
+
+    /// An open task waiting on another open task.
+    pub fn is_blocked(&self, open: &HashSet<u64>) -> bool {
+        !self.is_completed() && self.open_dependencies(open).next().is_some()
+    }
+
+    /// An open task whose due date is before `today`.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        !self.is_completed() && self.due.is_some_and(|due| due < today)
+    }
+
+    /// A score for how pressing the task is; higher means do it sooner.
+    ///
+    /// It adds up three parts: the priority, how close the due date is (up to 12
+    /// points, reached a week after the due date has passed) and the task's age
+    /// (up to 2 points, reached after a year).
+    pub fn urgency(&self, now: DateTime<Utc>) -> f64 {
+        let priority = self.priority.map_or(0.0, Priority::weight);
+
+        let due = match self.due {
+            Some(due) => {
+                let today = now.with_timezone(&Local).date_naive();
+                let days_left = (due - today).num_days() as f64;
+                // Linear from 0.2 two weeks out to 1.0 a week overdue.
+                let factor = ((14.0 - days_left) / 21.0 * 0.8 + 0.2).clamp(0.2, 1.0);
+                12.0 * factor
+            }
+            None => 0.0,
+        };
+
+        let age_days = (now - self.created_at).num_days() as f64;
+        let age = 2.0 * (age_days / 365.0).clamp(0.0, 1.0);
+
+        priority + due + age
+    }
+}
+
+/// Check a tag given on the command line, dropping the optional leading `+`.
+pub fn parse_tag(s: &str) -> std::result::Result<String, String> {
+    let tag = s.strip_prefix('+').unwrap_or(s);
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid tag '{}'", s));
+    }
+    Ok(tag.to_string())
+}
+
+/// Check a project name given on the command line, dropping an optional `project:` prefix.
+pub fn parse_project(s: &str) -> std::result::Result<String, String> {
+    let project = s.strip_prefix("project:").unwrap_or(s);
+    if project.is_empty() || project.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid project '{}'", s));
+    }
+    Ok(project.to_string())
+}
+
+/// The orders `list` can sort tasks in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Most urgent first.
+    Urgency,
+    /// Earliest due date first; tasks without one go last.
+    Due,
+    /// Oldest first.
+    Created,
+    /// Highest priority first; tasks without one go last.
+    Priority,
 }
 
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "urgency" => Ok(SortKey::Urgency),
+            "due" => Ok(SortKey::Due),
+            "created" => Ok(SortKey::Created),
+            "priority" => Ok(SortKey::Priority),
+            _ => Err(format!(
+                "Unknown sort key '{}', expected urgency, due, created or priority",
+                s
+            )),
+        }
+    }
+}
 
+impl SortKey {
+    /// Sort tasks in place. The sort is stable, so ties keep journal order.
+    pub fn sort(self, tasks: &mut [&Task]) {
+        match self {
+            SortKey::Urgency => {
+                let now = Utc::now();
+                tasks.sort_by(|a, b| b.urgency(now).total_cmp(&a.urgency(now)));
+            }
+            SortKey::Due => tasks.sort_by_key(|task| (task.due.is_none(), task.due)),
+            SortKey::Created => tasks.sort_by_key(|task| task.created_at),
+            SortKey::Priority => tasks.sort_by_key(|task| Reverse(task.priority)),
+        }
+    }
+}
+
+/// How `list` should pick and order tasks.
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    pub show: Show,
+    pub sort: Option<SortKey>,
+    /// Only tasks carrying all of these tags.
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub query: Option<Query>,
+    /// `Some(true)` for only blocked tasks, `Some(false)` for only ready ones.
+    pub blocked: Option<bool>,
+}
+
+impl ListOptions {
+    pub fn includes(&self, task: &Task, open: &HashSet<u64>) -> bool {
+        self.show.includes(task)
+            && self.blocked.is_none_or(|blocked| task.is_blocked(open) == blocked)
+            && self.tags.iter().all(|tag| task.tags.contains(tag))
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|project| task.project.as_ref() == Some(project))
+            && self.query.as_ref().is_none_or(|query| query.matches(task))
+    }
+}
+
+/// Which tasks `list` should show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Show {
+    Open,
+    Completed,
+    All,
+}
+
+impl Show {
+    pub fn includes(self, task: &Task) -> bool {
+        match self {
+            Show::Open => !task.is_completed(),
+            Show::Completed => task.is_completed(),
+            Show::All => true,
+        }
+    }
+}
+
+/// Changes `modify` makes to a task. Fields left as `None` or empty keep
+/// their current value.
+#[derive(Debug, Default)]
+pub struct Modification {
+    pub text: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub priority: Option<Priority>,
+    pub project: Option<String>,
+    pub recurrence: Option<Recurrence>,
+    pub parent: Option<u64>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    /// Fields to unset, applied before the rest.
+    pub clear: Vec<Attribute>,
+}
+
+/// A task field that `modify --clear` can unset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Due,
+    Priority,
+    Project,
+    Tags,
+    Recurrence,
+    Parent,
+    Depends,
+}
+
+impl FromStr for Attribute {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "due" => Ok(Attribute::Due),
+            "priority" => Ok(Attribute::Priority),
+            "project" => Ok(Attribute::Project),
+            "tags" | "tag" => Ok(Attribute::Tags),
+            "recur" | "recurrence" => Ok(Attribute::Recurrence),
+            "parent" => Ok(Attribute::Parent),
+            "depends" => Ok(Attribute::Depends),
+            _ => Err(format!(
+                "Unknown field '{}', expected due, priority, project, tags, recur, parent or depends",
+                s
+            )),
+        }
+    }
+}
+
+impl Modification {
+    fn apply(&self, task: &mut Task) {
+        for attribute in &self.clear {
+            match attribute {
+                Attribute::Due => task.due = None,
+                Attribute::Priority => task.priority = None,
+                Attribute::Project => task.project = None,
+                Attribute::Tags => task.tags.clear(),
+                Attribute::Recurrence => task.recurrence = None,
+                Attribute::Parent => task.parent = None,
+                Attribute::Depends => task.depends.clear(),
+            }
+        }
+        if let Some(text) = &self.text {
+            task.text = text.clone();
+        }
+        if let Some(due) = self.due {
+            task.due = Some(due);
+        }
+        if let Some(priority) = self.priority {
+            task.priority = Some(priority);
+        }
+        if let Some(project) = &self.project {
+            task.project = Some(project.clone());
+        }
+        if let Some(recurrence) = &self.recurrence {
+            task.recurrence = Some(recurrence.clone());
+        }
+        if let Some(parent) = self.parent {
+            task.parent = Some(parent);
+        }
+        for tag in &self.remove_tags {
+            task.tags.remove(tag);
+        }
+        task.tags.extend(self.add_tags.iter().cloned());
+    }
+}
+
+/// Where `move` puts a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    Before(u64),
+    After(u64),
+    Top,
+    Bottom,
+}
+
+/// What completing a task did besides completing it.
+#[derive(Debug, Default)]
+pub struct Completion {
+    /// How many open subtasks were completed with it.
+    pub subtasks: usize,
+    /// The next occurrences of recurring tasks, with their due dates.
+    pub added: Vec<(u64, Option<NaiveDate>)>,
+}
+
+/// Everything stored in the journal file.
+///
+/// `next_id` only ever grows, so an ID is never handed out twice, even after the
+/// task that owned it has been removed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Journal {
+    /// The file format version, see `migrations`.
+    pub version: u32,
+    pub next_id: u64,
+    pub tasks: Vec<Task>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal {
+            version: CURRENT_VERSION,
+            next_id: 1,
+            tasks: Vec::new(),
+        }
+    }
+}
+
+impl Journal {
+    /// Add a task to the journal, giving it a fresh ID.
+    pub fn push(&mut self, mut task: Task) -> u64 {
+        task.id = self.next_id;
+        self.next_id += 1;
+        self.tasks.push(task);
+        self.next_id - 1
+    }
+
+    /// The IDs of every task below the given one, children before grandchildren.
+    pub fn descendants(&self, id: u64) -> Vec<u64> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::from([id]);
+        let mut queue = vec![id];
+        while let Some(parent) = queue.pop() {
+            for task in self.tasks.iter().filter(|task| task.parent == Some(parent)) {
+                // A hand-edited journal could contain a loop; don't follow it.
+                if seen.insert(task.id) {
+                    found.push(task.id);
+                    queue.push(task.id);
+                }
+            }
+        }
+        found
+    }
+
+    /// The IDs of every open task.
+    pub fn open_ids(&self) -> HashSet<u64> {
+        self.tasks.iter().filter(|task| !task.is_completed()).map(|task| task.id).collect()
+    }
+
+    /// A chain of dependencies leading from `from` to `to`, both included, if
+    /// there is one.
+    pub fn dependency_path(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        let depends: HashMap<u64, &BTreeSet<u64>> =
+            self.tasks.iter().map(|task| (task.id, &task.depends)).collect();
+        // Breadth first, remembering how each task was reached, so the chain
+        // reported is the shortest one.
+        let mut reached_from = HashMap::from([(from, from)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(reached_from[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in depends.get(&id).copied().into_iter().flatten() {
+                if let Entry::Vacant(entry) = reached_from.entry(next) {
+                    entry.insert(id);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the position of the task with the given ID.
+    pub fn position(&self, id: u64) -> Result<usize> {
+        self.tasks
+            .iter()
+            .position(|task| task.id == id)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("No task with ID {}", id)))
+    }
+
+    /// Add a new task, which can't be a subtask of a completed one.
+    pub fn add(&mut self, task: Task) -> Result<u64> {
+        if let Some(parent) = task.parent {
+            if self.tasks[self.position(parent)?].is_completed() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Task {} is already completed; can't add a subtask to it", parent),
+                ));
+            }
+        }
+        Ok(self.push(task))
+    }
+
+    /// Mark a task as done. It stays in the journal as history until purged.
+    ///
+    /// A task with open subtasks is only completed with `recursive`, which
+    /// completes the subtasks as well.
+    pub fn complete(&mut self, task_id: u64, recursive: bool) -> Result<Completion> {
+        if self.tasks[self.position(task_id)?].is_completed() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Task {} is already completed", task_id),
+            ));
+        }
+
+        let open: Vec<u64> = self
+            .descendants(task_id)
+            .into_iter()
+            .filter(|&id| self.position(id).is_ok_and(|position| !self.tasks[position].is_completed()))
+            .collect();
+        if !open.is_empty() && !recursive {
+            let ids: Vec<String> = open.iter().map(|id| format!("#{}", id)).collect();
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Task {} has {} open subtask(s) ({}); finish them first or use --recursive",
+                    task_id,
+                    open.len(),
+                    ids.join(", ")
+                ),
+            ));
+        }
+
+        // A recurring task comes back as a new task with the next due date.
+        let today = Local::now().date_naive();
+        let mut added = Vec::new();
+        for id in open.iter().copied().chain([task_id]) {
+            let position = self.position(id)?;
+            let task = &mut self.tasks[position];
+            task.complete();
+            if let Some(next) = task.next_occurrence(today) {
+                let due = next.due;
+                added.push((self.push(next), due));
+            }
+        }
+        Ok(Completion {
+            subtasks: open.len(),
+            added,
+        })
+    }
 
-pub fn add_task(journal_path: PathBuf, task: Task) -> Result<()> {
-    // Open the file.
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(journal_path)?;
+    /// Change fields of an existing task, keeping its ID and creation time.
+    pub fn modify(&mut self, task_id: u64, modification: &Modification) -> Result<()> {
+        let position = self.position(task_id)?;
+        modification.apply(&mut self.tasks[position]);
+        self.check_task(task_id)
+    }
+
+    /// Check that every task is well formed, see `check_task`, and that no ID
+    /// is used twice.
+    pub fn check(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for task in &self.tasks {
+            if !ids.insert(task.id) {
+                return Err(invalid(format!("Task ID {} is used more than once", task.id)));
+            }
+            if task.id == 0 || task.id >= self.next_id {
+                return Err(invalid(format!("Task ID {} was never handed out", task.id)));
+            }
+        }
+        self.tasks.iter().try_for_each(|task| self.check_task(task.id))
+    }
+
+    /// Check a task's fields the way the command line would, and that its
+    /// parent and dependencies exist without forming a loop.
+    pub fn check_task(&self, id: u64) -> Result<()> {
+        let task = &self.tasks[self.position(id)?];
+        let fail = |message: String| Err(invalid(format!("Task {}: {}", id, message)));
+
+        if task.text.trim().is_empty() {
+            return fail("the text can't be empty".to_string());
+        }
+        for tag in &task.tags {
+            if parse_tag(tag).as_deref() != Ok(tag.as_str()) {
+                return fail(format!("invalid tag '{}'", tag));
+            }
+        }
+        if let Some(project) = &task.project {
+            if parse_project(project).as_deref() != Ok(project.as_str()) {
+                return fail(format!("invalid project '{}'", project));
+            }
+        }
+        if task.is_completed() != task.completed_at.is_some() {
+            return fail("completed tasks need a completed_at time, and open ones can't have one".to_string());
+        }
+
+        if let Some(parent) = task.parent {
+            // Walk up from the parent; meeting the task again means a loop.
+            let mut chain = vec![id];
+            let mut current = Some(parent);
+            while let Some(ancestor) = current {
+                let Ok(position) = self.position(ancestor) else {
+                    return fail(format!("parent task {} doesn't exist", ancestor));
+                };
+                chain.push(ancestor);
+                if ancestor == id {
+                    let chain: Vec<String> = chain.iter().map(u64::to_string).collect();
+                    return fail(format!("parents form a loop: {}", chain.join(" -> ")));
+                }
+                current = self.tasks[position].parent;
+            }
+        }
+        for &dependency in &task.depends {
+            if self.position(dependency).is_err() {
+                return fail(format!("depends on task {}, which doesn't exist", dependency));
+            }
+            if let Some(path) = self.dependency_path(dependency, id) {
+                let chain: Vec<String> = std::iter::once(id).chain(path).map(|id| id.to_string()).collect();
+                return fail(format!("dependencies form a cycle: {}", chain.join(" -> ")));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+pub fn add_task(storage: &mut dyn Storage, task: Task) -> Result<()> {
+    let id = update_journal(storage, |journal| journal.add(task))?;
     /*
-    The question mark symbol (?) after that statement is used to propagate errors without writing too much boilerplate code. 
+    The question mark symbol (?) after that statement is used to propagate errors without writing too much boilerplate code.
     It's syntax sugar for early returning an error if that error matches with the return type of the function it's in.
-    
+
      */
+    println!("Added task {}.", id);
 
-     let mut tasks = collect_tasks(&file)?;
+    Ok(())
+}
 
-    // Write the modified task list back into the file.
-    tasks.push(task);
-    serde_json::to_writer(file, &tasks)?;
+/// Complete a task, see `Journal::complete`, and say what else that did.
+pub fn complete_task(storage: &mut dyn Storage, task_id: u64, recursive: bool) -> Result<()> {
+    let completion = update_journal(storage, |journal| journal.complete(task_id, recursive))?;
 
+    if completion.subtasks > 0 {
+        println!("Completed {} subtask(s) too.", completion.subtasks);
+    }
+    for (id, due) in completion.added {
+        if let Some(due) = due {
+            println!("Added task {}, next due {}.", id, due);
+        }
+    }
     Ok(())
 }
 
+/// Remove tasks from the journal for good.
+///
+/// With no IDs, every completed task is removed. Otherwise only the given
+/// tasks are, whether they are completed or not.
+pub fn purge_tasks(storage: &mut dyn Storage, task_ids: &[u64]) -> Result<()> {
+    let removed = update_journal(storage, |journal| {
+        // Check every ID up front so a typo doesn't leave a half-done purge.
+        for &id in task_ids {
+            journal.position(id)?;
+        }
+
+        let purged = |task: &Task| {
+            if task_ids.is_empty() {
+                task.is_completed()
+            } else {
+                task_ids.contains(&task.id)
+            }
+        };
+        let removed: HashMap<u64, Option<u64>> = journal
+            .tasks
+            .iter()
+            .filter(|task| purged(task))
+            .map(|task| (task.id, task.parent))
+            .collect();
+        journal.tasks.retain(|task| !removed.contains_key(&task.id));
 
+        // Subtasks of removed tasks move up to their nearest remaining ancestor,
+        // and dependencies on removed tasks go away.
+        for task in &mut journal.tasks {
+            while let Some(&grandparent) = task.parent.and_then(|parent| removed.get(&parent)) {
+                task.parent = grandparent;
+            }
+            task.depends.retain(|id| !removed.contains_key(id));
+        }
+        Ok(removed.len())
+    })?;
 
-pub fn complete_task(journal_path: PathBuf, task_position: usize) -> Result<()> {
-    // Open the file.
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(journal_path)?;
+    println!("Purged {} task(s).", removed);
+    Ok(())
+}
 
-    // Consume file's contents as a vector of tasks.
-    let mut tasks = collect_tasks(&file)?;
+/// Add tags to a task, or remove them from it.
+pub fn tag_task(storage: &mut dyn Storage, task_id: u64, tags: &[String], remove: bool) -> Result<()> {
+    update_journal(storage, |journal| {
+        let position = journal.position(task_id)?;
+        let task = &mut journal.tasks[position];
+        for tag in tags {
+            if remove {
+                task.tags.remove(tag);
+            } else {
+                task.tags.insert(tag.clone());
+            }
+        }
+        Ok(())
+    })
+}
 
-    // Remove the task.
-    if task_position == 0 || task_position > tasks.len() {
-        return Err(Error::new(ErrorKind::InvalidInput, "Invalid Task ID"));
-    }
-    tasks.remove(task_position - 1);
+/// Make a task depend on others, or stop depending on them.
+///
+/// Dependencies that would make a cycle are refused, naming the chain of tasks
+/// that would loop.
+pub fn depend_task(storage: &mut dyn Storage, task_id: u64, depends: &[u64], remove: bool) -> Result<()> {
+    update_journal(storage, |journal| {
+        journal.position(task_id)?;
+        for &dependency in depends {
+            if remove {
+                let position = journal.position(task_id)?;
+                journal.tasks[position].depends.remove(&dependency);
+                continue;
+            }
 
-    file.set_len(0)?;
+            journal.position(dependency)?;
+            if let Some(path) = journal.dependency_path(dependency, task_id) {
+                let chain: Vec<String> = std::iter::once(task_id).chain(path).map(|id| id.to_string()).collect();
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Task {} can't depend on task {}, that would make a cycle: {}",
+                        task_id,
+                        dependency,
+                        chain.join(" -> ")
+                    ),
+                ));
+            }
+            let position = journal.position(task_id)?;
+            journal.tasks[position].depends.insert(dependency);
+        }
+        Ok(())
+    })
+}
 
-    // Write the modified task list back into the file.
-    serde_json::to_writer(file, &tasks)?;
+pub fn modify_task(storage: &mut dyn Storage, task_id: u64, modification: &Modification) -> Result<()> {
+    update_journal(storage, |journal| journal.modify(task_id, modification))?;
+    println!("Modified task {}.", task_id);
     Ok(())
 }
 
-fn collect_tasks(mut file: &File) -> Result<Vec<Task>> {
-    file.seek(SeekFrom::Start(0))?; // Rewind the file before.
-    let tasks = match serde_json::from_reader(file) {
-        Ok(tasks) => tasks,
-        Err(e) if e.is_eof() => Vec::new(),
-        Err(e) => Err(e)?,
-    };
-    file.seek(SeekFrom::Start(0))?; // Rewind the file after.
-    Ok(tasks)
+/// Move a task to another place in the journal's order.
+pub fn move_task(storage: &mut dyn Storage, task_id: u64, place: Place) -> Result<()> {
+    update_journal(storage, |journal| {
+        let position = journal.position(task_id)?;
+        let task = journal.tasks.remove(position);
+        let target = match place {
+            Place::Top => 0,
+            Place::Bottom => journal.tasks.len(),
+            Place::Before(other) | Place::After(other) if other == task_id => {
+                return Err(invalid("A task can't be moved next to itself".to_string()));
+            }
+            Place::Before(other) => journal.position(other)?,
+            Place::After(other) => journal.position(other)? + 1,
+        };
+        journal.tasks.insert(target, task);
+        Ok(())
+    })
 }
 
-pub fn list_tasks(journal_path: PathBuf) -> Result<()> {
-    // Open the file.
-    let file = OpenOptions::new().read(true).open(journal_path)?;
-    // Parse the file and collect the tasks.
-    let tasks = collect_tasks(&file)?;
+/// Open one task, or the whole journal, in the user's editor as JSON and
+/// save the result once it checks out.
+///
+/// Editing the whole journal can also reorder and remove tasks. If the edited
+/// text is invalid, the user can go back to fix it or give up, in which case
+/// nothing changes.
+pub fn edit_tasks(storage: &mut dyn Storage, task_id: Option<u64>) -> Result<()> {
+    let changed = update_journal(storage, |journal| {
+        let original = match task_id {
+            Some(id) => serde_json::to_string_pretty(&journal.tasks[journal.position(id)?])?,
+            None => serde_json::to_string_pretty(&journal.tasks)?,
+        } + "\n";
 
-    // Enumerate and display tasks, if any.
-    if tasks.is_empty() {
-        println!("Task list is empty!");
+        let mut text = original.clone();
+        loop {
+            text = editor::edit(&text, "json")?;
+            if text == original {
+                return Ok(false);
+            }
+            match apply_edit(journal, task_id, &text) {
+                Ok(edited) => {
+                    *journal = edited;
+                    return Ok(true);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    if !editor::confirm("Edit again?")? {
+                        return Err(Error::new(ErrorKind::Interrupted, "Edit cancelled; nothing was changed"));
+                    }
+                }
+            }
+        }
+    })?;
+
+    if changed {
+        println!("Saved your changes.");
     } else {
-        let mut order: u32 = 1;
-        for task in tasks {
-            println!("{}: {}", order, task);
-            order += 1;
+        println!("Nothing changed.");
+    }
+    Ok(())
+}
+
+/// The journal with edited JSON applied: one task when `task_id` is given,
+/// otherwise the whole task list.
+fn apply_edit(journal: &Journal, task_id: Option<u64>, text: &str) -> Result<Journal> {
+    let mut edited = journal.clone();
+    match task_id {
+        Some(id) => {
+            let task: Task = serde_json::from_str(text)?;
+            if task.id != id {
+                return Err(invalid(format!("The task's ID can't be changed (was {}, now {})", id, task.id)));
+            }
+            let position = edited.position(id)?;
+            edited.tasks[position] = task;
+            edited.check_task(id)?;
+        }
+        None => {
+            edited.tasks = serde_json::from_str(text)?;
+            edited.check()?;
+        }
+    }
+    Ok(edited)
+}
+
+/// Print every tag in use with how many tasks carry it.
+pub fn list_tags(storage: &mut dyn Storage) -> Result<()> {
+    let journal = storage.load()?;
+
+    // Count open and total tasks per tag, sorted by tag name.
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for task in &journal.tasks {
+        for tag in &task.tags {
+            let (open, total) = counts.entry(tag).or_default();
+            if !task.is_completed() {
+                *open += 1;
+            }
+            *total += 1;
         }
     }
 
+    if counts.is_empty() {
+        println!("No tags in use.");
+    }
+    for (tag, (open, total)) in counts {
+        println!("+{:<20} {:>4} open {:>4} total", tag, open, total);
+    }
     Ok(())
 }
 
-use std::fmt;
+/// Copy every task from one storage backend into another.
+///
+/// The copy is read back and compared with the original, so nothing is lost
+/// silently. The target must not hold any tasks yet.
+pub fn migrate_journal(from: &mut dyn Storage, to: &mut dyn Storage) -> Result<()> {
+    let journal = from.load()?;
+    if !to.load()?.tasks.is_empty() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "The target journal already has tasks; refusing to overwrite them",
+        ));
+    }
+
+    to.save(&journal)?;
+    let copy = to.load()?;
+    if serde_json::to_value(&copy)? != serde_json::to_value(&journal)? {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "The copied journal doesn't match the original",
+        ));
+    }
+
+    println!("Copied {} task(s).", journal.tasks.len());
+    Ok(())
+}
+
+/// Load the journal, let `change` modify it, and save it back.
+///
+/// Nothing is saved if `change` fails, so a command either applies fully or
+/// not at all.
+pub fn update_journal<T>(
+    storage: &mut dyn Storage,
+    change: impl FnOnce(&mut Journal) -> Result<T>,
+) -> Result<T> {
+    let mut journal = storage.load()?;
+    let result = change(&mut journal)?;
+    storage.save(&journal)?;
+    Ok(result)
+}
+
+/// Print the tasks `options` picks, as a tree of subtasks in the text format
+/// and as a flat list of `columns` in the others.
+pub fn list_tasks(
+    storage: &mut dyn Storage,
+    options: ListOptions,
+    format: OutputFormat,
+    columns: &Columns,
+) -> Result<()> {
+    let journal = storage.load()?;
+    print_list(&journal, &options, format, columns);
+    Ok(())
+}
+
+/// The tasks `options` picks, in the order it asks for.
+pub fn select_tasks<'a>(journal: &'a Journal, options: &ListOptions) -> Vec<&'a Task> {
+    let open = journal.open_ids();
+    let mut tasks: Vec<&Task> = journal
+        .tasks
+        .iter()
+        .filter(|task| options.includes(task, &open))
+        .collect();
+    if let Some(sort) = options.sort {
+        sort.sort(&mut tasks);
+    }
+    tasks
+}
+
+/// Print the tasks `options` picks from a loaded journal, see `list_tasks`.
+pub fn print_list(journal: &Journal, options: &ListOptions, format: OutputFormat, columns: &Columns) {
+    let tasks = select_tasks(journal, options);
+    let open = journal.open_ids();
+
+    // Scripts get an empty list rather than a message.
+    if format.is_machine() {
+        output::print_tasks(journal, &tasks, format, columns);
+        return;
+    }
+    if tasks.is_empty() {
+        println!("Task list is empty!");
+        return;
+    }
+    if format == OutputFormat::Table {
+        output::print_tasks(journal, &tasks, format, columns);
+        return;
+    }
+
+    // Display tasks with their IDs, if any, with subtasks indented below
+    // their parent when both are shown.
+    let shown: HashSet<u64> = tasks.iter().map(|task| task.id).collect();
+    let mut roots = Vec::new();
+    let mut children: HashMap<u64, Vec<&Task>> = HashMap::new();
+    for &task in &tasks {
+        match task.parent.filter(|parent| shown.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(task),
+            None => roots.push(task),
+        }
+    }
+    let any_completed = tasks.iter().any(|task| task.is_completed());
+    let mut headers = vec!["ID", "Task", "Created", "Due", "Priority", "Project", "Tags"];
+    if any_completed {
+        headers.push("Done");
+    }
+    let mut table = Table::new(headers).flexible(1);
+    for task in roots {
+        add_tree_rows(&mut table, journal, task, 0, &children, &open, any_completed);
+    }
+    print!("{}", table.render(Terminal::stdout()));
+}
+
+fn add_tree_rows(
+    table: &mut Table,
+    journal: &Journal,
+    task: &Task,
+    depth: usize,
+    children: &HashMap<u64, Vec<&Task>>,
+    open: &HashSet<u64>,
+    done_column: bool,
+) {
+    // Roll up how many of the task's subtasks, at any depth, are done.
+    let descendants = journal.descendants(task.id);
+    let rollup = if descendants.is_empty() {
+        String::new()
+    } else {
+        let done = journal
+            .tasks
+            .iter()
+            .filter(|task| task.is_completed() && descendants.contains(&task.id))
+            .count();
+        format!(" ({}/{} done)", done, descendants.len())
+    };
+    let blockers: Vec<String> = task.open_dependencies(open).map(|id| format!("#{}", id)).collect();
+    let blocked = if blockers.is_empty() || task.is_completed() {
+        String::new()
+    } else {
+        format!(" (blocked by {})", blockers.join(", "))
+    };
+    let recurs = match &task.recurrence {
+        Some(recurrence) => format!(" (recurs {})", recurrence),
+        None => String::new(),
+    };
+    let today = Local::now().date_naive();
+    let date_format = &config::appearance().date_format;
+    let local_date = |at: DateTime<Utc>| at.with_timezone(&Local).format(date_format).to_string();
+
+    let mut cells = vec![
+        Cell::from(format!("{}{}", "  ".repeat(depth), task.id)),
+        Cell::from(format!("{}{}{}{}", task.text, rollup, blocked, recurs)),
+        Cell::from(local_date(task.created_at)),
+        Cell::coloured(
+            task.due.map(|due| due.format(date_format).to_string()).unwrap_or_default(),
+            output::due_colour(task, today),
+        ),
+        Cell::coloured(
+            task.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+            task.priority.and_then(output::priority_colour),
+        ),
+        Cell::from(task.project.clone().unwrap_or_default()),
+        Cell::from(task.tags.iter().map(|tag| format!("+{}", tag)).collect::<Vec<_>>().join(" ")),
+    ];
+    if done_column {
+        cells.push(Cell::from(task.completed_at.map(local_date).unwrap_or_default()));
+    }
+    table.push(cells, task.is_completed().then_some(Colour::Dim));
+
+    for child in children.get(&task.id).into_iter().flatten() {
+        add_tree_rows(table, journal, child, depth + 1, children, open, done_column);
+    }
+}
 
 impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let created_at = self.created_at.with_timezone(&Local).format("%F %H:%M");
-        write!(f, "{:<50} [{}]", self.text, created_at)
+        write!(f, "{} [{}]", table::pad(&self.text, 50), created_at)?;
+        if let Some(completed_at) = self.completed_at {
+            let completed_at = completed_at.with_timezone(&Local).format("%F %H:%M");
+            write!(f, " (done {})", completed_at)?;
+        }
+        if let Some(priority) = self.priority {
+            write!(f, " ({})", priority)?;
+        }
+        if let Some(project) = &self.project {
+            write!(f, " project:{}", project)?;
+        }
+        for tag in &self.tags {
+            write!(f, " +{}", tag)?;
+        }
+        if let Some(due) = self.due {
+            write!(f, " due {}", due)?;
+            if self.is_overdue(Local::now().date_naive()) {
+                write!(f, " !! OVERDUE")?;
+            }
+        }
+        if let Some(recurrence) = &self.recurrence {
+            write!(f, " (recurs {})", recurrence)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn done_keeps_history_until_purged() {
+        let mut storage = MemoryStore::default();
+        add_task(&mut storage, Task::new("first".into())).unwrap();
+        add_task(&mut storage, Task::new("second".into())).unwrap();
+
+        complete_task(&mut storage, 1, false).unwrap();
+        assert!(complete_task(&mut storage, 1, false).is_err());
+        let journal = storage.load().unwrap();
+        assert_eq!(journal.tasks.len(), 2);
+        assert!(journal.tasks[0].is_completed());
+        assert!(journal.tasks[0].completed_at.is_some());
+
+        purge_tasks(&mut storage, &[]).unwrap();
+        let ids: Vec<u64> = storage.load().unwrap().tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, vec![2]);
+    }
+
+    #[test]
+    fn failed_commands_change_nothing() {
+        let mut storage = MemoryStore::default();
+        add_task(&mut storage, Task::new("only".into())).unwrap();
+
+        // Task 7 doesn't exist, so nothing may be purged.
+        assert!(purge_tasks(&mut storage, &[1, 7]).is_err());
+        assert_eq!(storage.load().unwrap().tasks.len(), 1);
+    }
+
+    #[test]
+    fn completing_a_recurring_task_adds_the_next_one() {
+        let mut storage = MemoryStore::default();
+        let mut task = Task::new("water the plants".into());
+        let today = Local::now().date_naive();
+        task.due = Some(today);
+        task.recurrence = Some("every:3d".parse().unwrap());
+        task.tags.insert("home".into());
+        add_task(&mut storage, task).unwrap();
+
+        complete_task(&mut storage, 1, false).unwrap();
+        let journal = storage.load().unwrap();
+        assert_eq!(journal.tasks.len(), 2);
+        let next = &journal.tasks[1];
+        assert_eq!(next.id, 2);
+        assert!(!next.is_completed());
+        assert_eq!(next.due, Some(today + chrono::Duration::days(3)));
+        assert!(next.tags.contains("home"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parents_wait_for_their_subtasks() {
+        let mut storage = MemoryStore::default();
+        add_task(&mut storage, Task::new("migrate CI".into())).unwrap();
+        for (text, parent) in [("port jobs", 1), ("port secrets", 1), ("rotate tokens", 3)] {
+            let mut task = Task::new(text.into());
+            task.parent = Some(parent);
+            add_task(&mut storage, task).unwrap();
+        }
+        assert_eq!(storage.load().unwrap().descendants(1), vec![2, 3, 4]);
+
+        complete_task(&mut storage, 2, false).unwrap();
+        assert!(complete_task(&mut storage, 1, false).is_err());
+        complete_task(&mut storage, 1, true).unwrap();
+        assert!(storage.load().unwrap().tasks.iter().all(Task::is_completed));
+
+        // Purging a middle task keeps its subtasks under the grandparent.
+        purge_tasks(&mut storage, &[3]).unwrap();
+        assert_eq!(storage.load().unwrap().tasks[2].parent, Some(1));
+    }
+
+    #[test]
+    fn dependency_cycles_are_refused() {
+        let mut storage = MemoryStore::default();
+        for text in ["design", "build", "ship"] {
+            add_task(&mut storage, Task::new(text.into())).unwrap();
+        }
+        depend_task(&mut storage, 3, &[2], false).unwrap();
+        depend_task(&mut storage, 2, &[1], false).unwrap();
+
+        let error = depend_task(&mut storage, 1, &[3], false).unwrap_err();
+        assert!(error.to_string().ends_with("1 -> 3 -> 2 -> 1"), "{}", error);
+        assert!(depend_task(&mut storage, 1, &[1], false).is_err());
+
+        let journal = storage.load().unwrap();
+        let open = journal.open_ids();
+        let blocked: Vec<u64> = journal.tasks.iter().filter(|task| task.is_blocked(&open)).map(|task| task.id).collect();
+        assert_eq!(blocked, vec![2, 3]);
+    }
+
+    #[test]
+    fn edits_are_checked_before_saving() {
+        let mut journal = Journal::default();
+        journal.push(Task::new("first".into()));
+        journal.push(Task::new("second".into()));
+        let text = |tasks: &[Task]| serde_json::to_string(tasks).unwrap();
+
+        let mut tasks = journal.tasks.clone();
+        tasks.swap(0, 1);
+        tasks[0].text = "second, reworded".into();
+        let edited = apply_edit(&journal, None, &text(&tasks)).unwrap();
+        assert_eq!(edited.tasks[0].text, "second, reworded");
+        assert_eq!(edited.tasks[0].created_at.timestamp(), journal.tasks[1].created_at.timestamp());
+
+        let mut tasks = journal.tasks.clone();
+        tasks[0].parent = Some(2);
+        tasks[1].parent = Some(1);
+        let error = apply_edit(&journal, None, &text(&tasks)).unwrap_err();
+        assert!(error.to_string().contains("1 -> 2 -> 1"), "{}", error);
+
+        let mut tasks = journal.tasks.clone();
+        tasks[1].id = 1;
+        assert!(apply_edit(&journal, None, &text(&tasks)).is_err());
+        assert!(apply_edit(&journal, Some(1), "{ not json").is_err());
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let mut journal = Journal::default();
+        journal.push(Task::new("first".into()));
+        let second = journal.push(Task::new("second".into()));
+
+        // Removing the newest task must not free its ID.
+        let position = journal.position(second).unwrap();
+        journal.tasks.remove(position);
+
+        assert_eq!(journal.push(Task::new("third".into())), 3);
+    }
+
+    #[test]
+    fn urgency_grows_with_priority_and_closer_due_dates() {
+        let now = Utc::now();
+        let today = now.with_timezone(&Local).date_naive();
+
+        let plain = Task::new("plain".into());
+        let mut high = Task::new("high".into());
+        high.priority = Some(Priority::High);
+        let mut due_soon = Task::new("due soon".into());
+        due_soon.due = Some(today + chrono::Duration::days(1));
+        let mut due_later = Task::new("due later".into());
+        due_later.due = Some(today + chrono::Duration::days(30));
+
+        assert!(high.urgency(now) > plain.urgency(now));
+        assert!(due_soon.urgency(now) > due_later.urgency(now));
+        assert!(due_later.urgency(now) > plain.urgency(now));
+    }
+}
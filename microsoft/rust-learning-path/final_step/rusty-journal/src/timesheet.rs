@@ -0,0 +1,535 @@
+//! Time tracking: `start` and `stop` a timer on a task, `log` time after the
+//! fact, and add it all up with `timesheet`.
+//!
+//! Time is kept on each task as entries with a start and an end. At most one
+//! entry in the whole journal has no end yet, the running timer, and since it
+//! is saved with the journal it keeps running between commands. Completing a
+//! task stops its timer.
+
+use crate::output::{self, OutputFormat};
+use crate::storage::Storage;
+use crate::table::{self, Colour, Table, Terminal};
+use crate::tasks::{update_journal, Journal, Task};
+use chrono::serde::{ts_seconds, ts_seconds_option};
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::str::FromStr;
+
+/// A stretch of time spent on a task.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TimeEntry {
+    #[serde(with = "ts_seconds")]
+    pub start: DateTime<Utc>,
+
+    /// `None` while the timer is running.
+    #[serde(default, with = "ts_seconds_option", skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// How `timesheet` rounds the time spent on each task each day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Nearest,
+    Up,
+    Down,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" => Ok(Rounding::Nearest),
+            "up" => Ok(Rounding::Up),
+            "down" => Ok(Rounding::Down),
+            _ => Err(format!("Unknown rounding '{}', expected nearest, up or down", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rounding::Nearest => "nearest",
+            Rounding::Up => "up",
+            Rounding::Down => "down",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The longest length of time `parse_duration` accepts, in hours.
+const MAX_HOURS: i64 = 24;
+
+/// Read a length of time such as `1h30m`, `90m`, `1.5h` or `1:30`, of at
+/// most a day.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let invalid = || format!("Invalid length of time '{}', expected e.g. 1h30m, 90m, 1.5h or 1:30", s);
+    let minutes = match s.split_once(':') {
+        Some((hours, minutes)) => {
+            let hours: i64 = hours.parse().map_err(|_| invalid())?;
+            let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+            if hours < 0 || !(0..60).contains(&minutes) || hours > MAX_HOURS {
+                return Err(invalid());
+            }
+            (hours * 60 + minutes) as f64
+        }
+        None => {
+            let mut total = 0.0;
+            let mut rest = s.trim().to_lowercase();
+            if rest.is_empty() {
+                return Err(invalid());
+            }
+            while !rest.is_empty() {
+                let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.').ok_or_else(invalid)?;
+                let number: f64 = rest[..split].parse().map_err(|_| invalid())?;
+                let unit = rest[split..].chars().next().ok_or_else(invalid)?;
+                total += match unit {
+                    'h' => number * 60.0,
+                    'm' => number,
+                    _ => return Err(invalid()),
+                };
+                rest = rest[split + 1..].to_string();
+            }
+            total
+        }
+    };
+    if !minutes.is_finite() || minutes > (MAX_HOURS * 60) as f64 {
+        return Err(format!("'{}' is longer than {} hours", s, MAX_HOURS));
+    }
+    let seconds = (minutes * 60.0).round() as i64;
+    if seconds <= 0 {
+        return Err(invalid());
+    }
+    Duration::try_seconds(seconds).ok_or_else(invalid)
+}
+
+/// Read a time of day such as `14:30`.
+pub fn parse_time(s: &str) -> std::result::Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| format!("Invalid time '{}', expected HH:MM such as 14:30", s))
+}
+
+/// The running timer: the task it runs on and when it started.
+pub fn running(journal: &Journal) -> Option<(u64, DateTime<Utc>)> {
+    journal.tasks.iter().find_map(|task| {
+        task.time
+            .iter()
+            .find(|entry| entry.end.is_none())
+            .map(|entry| (task.id, entry.start))
+    })
+}
+
+/// Start the timer on a task, stopping the one that was running, if any.
+/// Returns the stopped task and how long its timer ran.
+fn start(journal: &mut Journal, task_id: u64, now: DateTime<Utc>) -> Result<Option<(u64, Duration)>> {
+    let position = journal.position(task_id)?;
+    if journal.tasks[position].is_completed() {
+        return Err(invalid(format!("Task {} is already completed", task_id)));
+    }
+    if running(journal).is_some_and(|(id, _)| id == task_id) {
+        return Err(invalid(format!("Task {} is already running", task_id)));
+    }
+    let stopped = match running(journal) {
+        Some(_) => Some(stop(journal, now)?),
+        None => None,
+    };
+    journal.tasks[position].time.push(TimeEntry { start: now, end: None });
+    Ok(stopped)
+}
+
+/// Stop the running timer, returning its task and how long it ran.
+fn stop(journal: &mut Journal, now: DateTime<Utc>) -> Result<(u64, Duration)> {
+    for task in &mut journal.tasks {
+        if let Some(entry) = task.time.iter_mut().find(|entry| entry.end.is_none()) {
+            let end = now.max(entry.start);
+            entry.end = Some(end);
+            return Ok((task.id, end - entry.start));
+        }
+    }
+    Err(invalid("No task is running".to_string()))
+}
+
+/// Record time spent on a task after the fact.
+fn log(journal: &mut Journal, task_id: u64, start: DateTime<Utc>, end: DateTime<Utc>, now: DateTime<Utc>) -> Result<()> {
+    let position = journal.position(task_id)?;
+    if end > now {
+        return Err(invalid("Time can't be logged in the future".to_string()));
+    }
+    journal.tasks[position].time.push(TimeEntry { start, end: Some(end) });
+    Ok(())
+}
+
+pub fn start_task(storage: &mut dyn Storage, task_id: u64) -> Result<()> {
+    let stopped = update_journal(storage, |journal| start(journal, task_id, Utc::now()))?;
+    if let Some((id, ran)) = stopped {
+        println!("Stopped task {} after {}.", id, hours_minutes(ran.num_seconds()));
+    }
+    println!("Started task {}.", task_id);
+    Ok(())
+}
+
+pub fn stop_task(storage: &mut dyn Storage) -> Result<()> {
+    let (id, ran) = update_journal(storage, |journal| stop(journal, Utc::now()))?;
+    println!("Stopped task {} after {}.", id, hours_minutes(ran.num_seconds()));
+    Ok(())
+}
+
+/// Log `spent` on a task on `date`. The entry starts at `at`, or, without
+/// it, ends now for today and starts at 09:00 on other days.
+pub fn log_time(
+    storage: &mut dyn Storage,
+    task_id: u64,
+    spent: Duration,
+    date: Option<NaiveDate>,
+    at: Option<NaiveTime>,
+) -> Result<()> {
+    let now = Utc::now();
+    let today = Local::now().date_naive();
+    let date = date.unwrap_or(today);
+    let out_of_range = || invalid(format!("Can't log {} on {}", hours_minutes(spent.num_seconds()), date));
+    let start = match at {
+        None if date == today => now.checked_sub_signed(spent).ok_or_else(out_of_range)?,
+        at => {
+            let at = at.unwrap_or(NaiveTime::from_hms_opt(9, 0, 0).expect("valid time"));
+            Local
+                .from_local_datetime(&date.and_time(at))
+                .earliest()
+                .ok_or_else(|| invalid(format!("{} {} doesn't exist here", date, at)))?
+                .with_timezone(&Utc)
+        }
+    };
+    let end = start.checked_add_signed(spent).ok_or_else(out_of_range)?;
+    update_journal(storage, |journal| log(journal, task_id, start, end, now))?;
+    println!("Logged {} on task {} for {}.", hours_minutes(spent.num_seconds()), task_id, date);
+    Ok(())
+}
+
+/// What `timesheet` prints: the seconds spent per task, tag and day.
+#[derive(Debug)]
+struct Sheet {
+    days: Vec<NaiveDate>,
+    /// `(id, text)` and the seconds per day, for tasks with time in the range.
+    tasks: Vec<((u64, String), BTreeMap<NaiveDate, i64>)>,
+    /// `+tag`, or `(none)` for untagged tasks, and the seconds per day.
+    tags: BTreeMap<String, BTreeMap<NaiveDate, i64>>,
+    round: Option<(Duration, Rounding)>,
+    running: Option<(u64, DateTime<Utc>)>,
+}
+
+impl Sheet {
+    fn day_totals(&self) -> BTreeMap<NaiveDate, i64> {
+        let mut totals = BTreeMap::new();
+        for (_, days) in &self.tasks {
+            for (&day, &seconds) in days {
+                *totals.entry(day).or_insert(0) += seconds;
+            }
+        }
+        totals
+    }
+}
+
+/// Print the time spent from `from` to `to`, both included, with each task's
+/// time for a day rounded to a multiple of `round`.
+pub fn print_timesheet(
+    storage: &mut dyn Storage,
+    from: NaiveDate,
+    to: NaiveDate,
+    round: Option<(Duration, Rounding)>,
+    format: OutputFormat,
+) -> Result<()> {
+    let journal = storage.load()?;
+    let sheet = build(&journal, from, to, round, Utc::now());
+    print!("{}", render(&sheet, format, Terminal::stdout()));
+    Ok(())
+}
+
+fn build(journal: &Journal, from: NaiveDate, to: NaiveDate, round: Option<(Duration, Rounding)>, now: DateTime<Utc>) -> Sheet {
+    let days: Vec<NaiveDate> = from.iter_days().take_while(|day| *day <= to).collect();
+    let mut tasks = Vec::new();
+    let mut tags: BTreeMap<String, BTreeMap<NaiveDate, i64>> = BTreeMap::new();
+    for task in &journal.tasks {
+        let spent = spent_per_day(task, from, to, now, round);
+        if spent.is_empty() {
+            continue;
+        }
+        let mut names: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+        if names.is_empty() {
+            names.push("(none)".to_string());
+        }
+        for name in names {
+            let tag = tags.entry(name).or_default();
+            for (&day, &seconds) in &spent {
+                *tag.entry(day).or_insert(0) += seconds;
+            }
+        }
+        tasks.push(((task.id, task.text.clone()), spent));
+    }
+    Sheet {
+        days,
+        tasks,
+        tags,
+        round,
+        running: running(journal),
+    }
+}
+
+/// The rounded seconds spent on `task` on each local day from `from` to `to`
+/// that it has any time on. A running timer counts up to `now`.
+fn spent_per_day(
+    task: &Task,
+    from: NaiveDate,
+    to: NaiveDate,
+    now: DateTime<Utc>,
+    round: Option<(Duration, Rounding)>,
+) -> BTreeMap<NaiveDate, i64> {
+    let mut spent = BTreeMap::new();
+    for entry in &task.time {
+        let mut at = entry.start;
+        let end = entry.end.unwrap_or(now);
+        // Split the entry at local midnights.
+        while at < end {
+            let day = at.with_timezone(&Local).date_naive();
+            let midnight = day
+                .checked_add_days(Days::new(1))
+                .and_then(|next| Local.from_local_datetime(&next.and_time(NaiveTime::MIN)).earliest())
+                .map(|midnight| midnight.with_timezone(&Utc))
+                .filter(|&midnight| midnight > at)
+                .unwrap_or(end);
+            let cut = midnight.min(end);
+            if (from..=to).contains(&day) {
+                *spent.entry(day).or_insert(0) += (cut - at).num_seconds();
+            }
+            at = cut;
+        }
+    }
+    if let Some((step, rounding)) = round {
+        let step = step.num_seconds().max(1);
+        for seconds in spent.values_mut() {
+            *seconds = match rounding {
+                Rounding::Nearest => (*seconds + step / 2) / step * step,
+                Rounding::Up => (*seconds + step - 1) / step * step,
+                Rounding::Down => *seconds / step * step,
+            };
+        }
+    }
+    spent.retain(|_, seconds| *seconds > 0);
+    spent
+}
+
+fn render(sheet: &Sheet, format: OutputFormat, terminal: Terminal) -> String {
+    let hours = |seconds: i64| -> Value { json!((seconds as f64 / 36.0).round() / 100.0) };
+    let by_day = |days: &BTreeMap<NaiveDate, i64>| -> Value {
+        let days: Map<String, Value> = days.iter().map(|(day, &seconds)| (day.to_string(), hours(seconds))).collect();
+        Value::Object(days)
+    };
+    let total = |days: &BTreeMap<NaiveDate, i64>| -> i64 { days.values().sum() };
+    match format {
+        OutputFormat::Json => {
+            let value = json!({
+                "from": sheet.days.first(),
+                "to": sheet.days.last(),
+                "rounding": sheet.round.map(|(step, rounding)| json!({
+                    "minutes": step.num_minutes(),
+                    "mode": rounding.to_string(),
+                })),
+                "hours": hours(total(&sheet.day_totals())),
+                "days": by_day(&sheet.day_totals()),
+                "tasks": sheet.tasks.iter().map(|((id, text), days)| json!({
+                    "id": id,
+                    "text": text,
+                    "hours": hours(total(days)),
+                    "days": by_day(days),
+                })).collect::<Vec<_>>(),
+                "tags": sheet.tags.iter().map(|(tag, days)| json!({
+                    "tag": tag,
+                    "hours": hours(total(days)),
+                    "days": by_day(days),
+                })).collect::<Vec<_>>(),
+            });
+            serde_json::to_string_pretty(&value).unwrap_or_default() + "\n"
+        }
+        OutputFormat::Jsonl | OutputFormat::Csv | OutputFormat::Tsv => {
+            // Flat `(section, key, date, hours)` rows, for spreadsheets.
+            let mut rows = Vec::new();
+            for ((id, _), days) in &sheet.tasks {
+                rows.extend(days.iter().map(|(day, &seconds)| ("task", id.to_string(), *day, seconds)));
+            }
+            for (tag, days) in &sheet.tags {
+                rows.extend(days.iter().map(|(day, &seconds)| ("tag", tag.clone(), *day, seconds)));
+            }
+            rows.extend(sheet.day_totals().into_iter().map(|(day, seconds)| ("day", "all".to_string(), day, seconds)));
+
+            if format == OutputFormat::Jsonl {
+                return rows
+                    .into_iter()
+                    .map(|(section, key, day, seconds)| {
+                        json!({ "section": section, "key": key, "date": day, "hours": hours(seconds) }).to_string() + "\n"
+                    })
+                    .collect();
+            }
+            let header = ["section", "key", "date", "hours"].map(String::from);
+            let mut out = output::delimited_line(format, &header);
+            for (section, key, day, seconds) in rows {
+                let cells = [section.to_string(), key, day.to_string(), hours(seconds).to_string()];
+                out.push_str(&output::delimited_line(format, &cells));
+            }
+            out
+        }
+        OutputFormat::Text | OutputFormat::Table => render_text(sheet, terminal),
+    }
+}
+
+fn render_text(sheet: &Sheet, terminal: Terminal) -> String {
+    let (Some(&from), Some(&to)) = (sheet.days.first(), sheet.days.last()) else {
+        return String::new();
+    };
+    let mut out = format!("Time spent from {} to {}", from.format("%a %Y-%m-%d"), to.format("%a %Y-%m-%d"));
+    if let Some((step, rounding)) = sheet.round {
+        let step = step.num_minutes();
+        out += &match rounding {
+            Rounding::Nearest => format!(", rounded to the nearest {} minutes", step),
+            Rounding::Up | Rounding::Down => format!(", rounded {} to {} minutes", rounding, step),
+        };
+    }
+    out = if terminal.colour { table::paint(&out, Colour::Bold) } else { out } + "\n";
+
+    if sheet.tasks.is_empty() {
+        out += "No time tracked.\n";
+    } else {
+        let day_headers: Vec<String> = sheet.days.iter().map(|day| day.format("%a %d").to_string()).collect();
+        let cells = |days: &BTreeMap<NaiveDate, i64>| -> Vec<table::Cell> {
+            let mut cells: Vec<table::Cell> = sheet
+                .days
+                .iter()
+                .map(|day| days.get(day).map(|&seconds| hours_minutes(seconds)).unwrap_or_default().into())
+                .collect();
+            cells.push(hours_minutes(days.values().sum()).into());
+            cells
+        };
+
+        let headers = ["ID".to_string(), "Task".to_string()]
+            .into_iter()
+            .chain(day_headers.iter().cloned())
+            .chain(["Total".to_string()]);
+        let mut tasks = Table::new(headers).flexible(1);
+        for ((id, text), days) in &sheet.tasks {
+            let mut row = vec![id.to_string().into(), text.as_str().into()];
+            row.extend(cells(days));
+            tasks.push(row, None);
+        }
+        let mut row = vec!["".into(), "Total".into()];
+        row.extend(cells(&sheet.day_totals()));
+        tasks.push(row, Some(Colour::Bold));
+        out.push('\n');
+        out += &tasks.render(terminal);
+
+        let headers = ["Tag".to_string()]
+            .into_iter()
+            .chain(day_headers.iter().cloned())
+            .chain(["Total".to_string()]);
+        let mut tags = Table::new(headers);
+        for (tag, days) in &sheet.tags {
+            let mut row = vec![tag.as_str().into()];
+            row.extend(cells(days));
+            tags.push(row, None);
+        }
+        out.push('\n');
+        out += &tags.render(terminal);
+    }
+
+    if let Some((id, started)) = sheet.running {
+        let started = started.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        out += &format!("\nTask {} has been running since {}.\n", id, started);
+    }
+    out
+}
+
+/// The Monday to Sunday week `date` is in.
+pub fn week_of(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let monday = date - Days::new(date.weekday().num_days_from_monday().into());
+    (monday, monday + Days::new(6))
+}
+
+/// Seconds as `h:mm`.
+fn hours_minutes(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        let time = chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&time).unwrap().with_timezone(&Utc)
+    }
+
+    fn journal() -> Journal {
+        let mut journal = Journal::default();
+        let mut deploy = Task::new("deploy".to_string());
+        deploy.tags.insert("release".to_string());
+        journal.push(deploy);
+        journal.push(Task::new("docs".to_string()));
+        journal
+    }
+
+    #[test]
+    fn one_timer_runs_at_a_time() {
+        let mut journal = journal();
+        assert_eq!(start(&mut journal, 1, at("2026-10-19 09:00")).unwrap(), None);
+        assert!(start(&mut journal, 1, at("2026-10-19 09:10")).is_err());
+
+        let stopped = start(&mut journal, 2, at("2026-10-19 10:30")).unwrap();
+        assert_eq!(stopped, Some((1, Duration::minutes(90))));
+        assert_eq!(running(&journal), Some((2, at("2026-10-19 10:30"))));
+
+        assert_eq!(stop(&mut journal, at("2026-10-19 11:00")).unwrap(), (2, Duration::minutes(30)));
+        assert!(stop(&mut journal, at("2026-10-19 11:05")).is_err());
+        assert_eq!(running(&journal), None);
+    }
+
+    #[test]
+    fn time_is_split_by_day_and_rounded() {
+        let mut journal = journal();
+        let now = at("2026-10-25 12:00");
+        log(&mut journal, 1, at("2026-10-19 23:00"), at("2026-10-20 01:10"), now).unwrap();
+        log(&mut journal, 2, at("2026-10-21 09:00"), at("2026-10-21 09:05"), now).unwrap();
+        assert!(log(&mut journal, 2, at("2026-10-25 11:00"), at("2026-10-25 13:00"), now).is_err());
+
+        let (from, to) = week_of("2026-10-22".parse().unwrap());
+        let sheet = build(&journal, from, to, None, now);
+        let deploy = &sheet.tasks[0].1;
+        assert_eq!(deploy.values().copied().collect::<Vec<_>>(), [3600, 4200]);
+        assert_eq!(sheet.tags.keys().collect::<Vec<_>>(), ["(none)", "+release"]);
+
+        let sheet = build(&journal, from, to, Some((Duration::minutes(15), Rounding::Nearest)), now);
+        assert_eq!(sheet.tasks[0].1.values().copied().collect::<Vec<_>>(), [3600, 4500]);
+        assert_eq!(sheet.tasks.len(), 1, "5 minutes round down to nothing");
+        let sheet = build(&journal, from, to, Some((Duration::minutes(15), Rounding::Up)), now);
+        assert_eq!(sheet.tasks[1].1.values().copied().collect::<Vec<_>>(), [900]);
+    }
+
+    #[test]
+    fn durations_read_several_ways() {
+        for text in ["1h30m", "90m", "1.5h", "1:30"] {
+            assert_eq!(parse_duration(text), Ok(Duration::minutes(90)), "{}", text);
+        }
+        assert_eq!(parse_duration("24h"), Ok(Duration::hours(24)));
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("3 days").is_err());
+        assert!(parse_duration("1:-5").is_err());
+        assert!(parse_duration("-1:30").is_err());
+        assert!(parse_duration("24:01").is_err());
+        assert!(parse_duration("99999999999999h").is_err());
+        assert!(parse_duration(&format!("{}m", "9".repeat(400))).is_err());
+    }
+}
@@ -0,0 +1,546 @@
+//! A full-screen interface for browsing and changing tasks with the keyboard.
+//!
+//! Changes go through the same `Journal` operations as the commands, each one
+//! recorded in the undo history under the command's name. Drawing only reads
+//! `App`, so tests render it to ratatui's `TestBackend` and compare snapshots.
+//!
+//! The journal lock is only held while loading or changing the journal, so
+//! commands run from other shells don't wait for the interface to close. It
+//! reloads the journal every few seconds to show what they changed.
+
+use crate::history::Recorder;
+use crate::lock::JournalLock;
+use crate::quickadd::QuickAdd;
+use crate::query::Query;
+use crate::storage::Storage;
+use crate::tasks::{self, Journal, ListOptions, Show, Task};
+use crate::{config, output, table};
+use chrono::{Local, NaiveDate};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the journal is reloaded while no keys are pressed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+const HELP: &str = "a add  A subtask  d done  D with subtasks  e edit  / filter  c completed  q quit";
+
+/// What the keyboard is doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Typing a query in the `list` query language.
+    Filter(String),
+    /// Typing the text of a new task.
+    Add { text: String, parent: Option<u64> },
+}
+
+/// The line under the task list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// The state of the interface between key presses.
+pub struct App {
+    journal: Journal,
+    options: ListOptions,
+    /// The query behind `options.query`, as typed.
+    filter: String,
+    /// Index into the visible tasks.
+    selected: usize,
+    mode: Mode,
+    message: Option<Message>,
+    today: NaiveDate,
+    history_depth: usize,
+    /// The journal to lock and how long to wait for it; tests don't lock.
+    lock: Option<(PathBuf, Duration)>,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(journal: Journal, history_depth: usize, today: NaiveDate) -> App {
+        App {
+            journal,
+            options: ListOptions {
+                show: Show::Open,
+                sort: None,
+                tags: Vec::new(),
+                project: None,
+                query: None,
+                blocked: None,
+            },
+            filter: String::new(),
+            selected: 0,
+            mode: Mode::Browse,
+            message: None,
+            today,
+            history_depth,
+            lock: None,
+            quit: false,
+        }
+    }
+
+    /// The tasks shown, in journal order.
+    fn visible(&self) -> Vec<&Task> {
+        let open = self.journal.open_ids();
+        self.journal
+            .tasks
+            .iter()
+            .filter(|task| self.options.includes(task, &open))
+            .collect()
+    }
+
+    fn selected_id(&self) -> Option<u64> {
+        self.visible().get(self.selected).map(|task| task.id)
+    }
+
+    fn select_id(&mut self, id: u64) {
+        if let Some(index) = self.visible().iter().position(|task| task.id == id) {
+            self.selected = index;
+        }
+    }
+
+    /// Handle a key press. Returns the ID of a task to open in the editor,
+    /// which needs the terminal back, so the caller does it.
+    pub fn handle_key(&mut self, key: KeyEvent, storage: &mut dyn Storage) -> Option<u64> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return None;
+        }
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => return self.browse(key, storage),
+            Mode::Filter(mut query) => match key.code {
+                KeyCode::Enter => self.apply_filter(query),
+                KeyCode::Esc => {}
+                code => {
+                    edit_line(&mut query, code);
+                    self.mode = Mode::Filter(query);
+                }
+            },
+            Mode::Add { mut text, parent } => match key.code {
+                KeyCode::Enter if !text.trim().is_empty() => {
+                    let mut task = match QuickAdd::parse(&text, self.today) {
+                        Ok(parsed) => parsed.into_task(self.today),
+                        Err(error) => {
+                            self.message = Some(Message::Error(error));
+                            self.mode = Mode::Add { text, parent };
+                            return None;
+                        }
+                    };
+                    task.parent = parent;
+                    if let Some(id) = self.change(storage, "add", |journal| journal.add(task)) {
+                        self.message = Some(Message::Info(format!("Added task {}.", id)));
+                        self.select_id(id);
+                    }
+                }
+                KeyCode::Enter | KeyCode::Esc => {}
+                code => {
+                    edit_line(&mut text, code);
+                    self.mode = Mode::Add { text, parent };
+                }
+            },
+        }
+        None
+    }
+
+    fn browse(&mut self, key: KeyEvent, storage: &mut dyn Storage) -> Option<u64> {
+        let count = self.visible().len();
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('j') | KeyCode::Down => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+            KeyCode::Char('G') | KeyCode::End => self.selected = count.saturating_sub(1),
+            KeyCode::Char('/') => self.mode = Mode::Filter(self.filter.clone()),
+            KeyCode::Char('c') => {
+                self.options.show = match self.options.show {
+                    Show::Open => Show::All,
+                    _ => Show::Open,
+                };
+                self.clamp();
+            }
+            KeyCode::Char('a') => {
+                self.mode = Mode::Add {
+                    text: String::new(),
+                    parent: None,
+                }
+            }
+            KeyCode::Char('A') => {
+                if let Some(parent) = self.selected_id() {
+                    self.mode = Mode::Add {
+                        text: String::new(),
+                        parent: Some(parent),
+                    }
+                }
+            }
+            KeyCode::Char(key @ ('d' | 'D')) => {
+                let id = self.selected_id()?;
+                if let Some(completion) = self.change(storage, "done", |journal| journal.complete(id, key == 'D')) {
+                    let mut message = format!("Completed task {}.", id);
+                    if completion.subtasks > 0 {
+                        message += &format!(" Completed {} subtask(s) too.", completion.subtasks);
+                    }
+                    for (id, due) in completion.added {
+                        if let Some(due) = due {
+                            message += &format!(" Added task {}, next due {}.", id, due);
+                        }
+                    }
+                    self.message = Some(Message::Info(message));
+                }
+            }
+            KeyCode::Char('e') => return self.selected_id(),
+            _ => {}
+        }
+        None
+    }
+
+    fn apply_filter(&mut self, query: String) {
+        let query = query.trim().to_string();
+        if query.is_empty() {
+            self.options.query = None;
+        } else {
+            match Query::parse(&query) {
+                Ok(parsed) => self.options.query = Some(parsed),
+                Err(e) => {
+                    // Only the first line; the rest underlines the query.
+                    let message = e.to_string().lines().next().unwrap_or_default().to_string();
+                    self.message = Some(Message::Error(message));
+                    self.mode = Mode::Filter(query);
+                    return;
+                }
+            }
+        }
+        self.filter = query;
+        self.selected = 0;
+    }
+
+    /// Change the journal the way a command would, recording the change for
+    /// `undo` under `label`. Errors are shown rather than returned.
+    fn change<T>(
+        &mut self,
+        storage: &mut dyn Storage,
+        label: &str,
+        change: impl FnOnce(&mut Journal) -> Result<T>,
+    ) -> Option<T> {
+        let _lock = self.lock(true).map_err(|e| self.message = Some(Message::Error(e.to_string()))).ok()?;
+        let mut recorder = Recorder::new(storage, label, self.history_depth);
+        let result = tasks::update_journal(&mut recorder, change);
+        self.reload(storage);
+        result.map_err(|e| self.message = Some(Message::Error(e.to_string()))).ok()
+    }
+
+    /// Take the journal lock for one load or change. Without `wait`, give up
+    /// straight away if another process has it.
+    fn lock(&self, wait: bool) -> Result<Option<JournalLock>> {
+        let Some((path, timeout)) = &self.lock else {
+            return Ok(None);
+        };
+        let timeout = if wait { *timeout } else { Duration::ZERO };
+        JournalLock::acquire(path, timeout).map(Some)
+    }
+
+    /// Pick up what other commands changed, unless one is busy with the journal.
+    fn refresh(&mut self, storage: &mut dyn Storage) {
+        if let Ok(_lock) = self.lock(false) {
+            self.reload(storage);
+        }
+    }
+
+    /// Load the journal again; the caller holds the lock.
+    fn reload(&mut self, storage: &mut dyn Storage) {
+        match storage.load() {
+            Ok(journal) => self.journal = journal,
+            Err(e) => self.message = Some(Message::Error(e.to_string())),
+        }
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.selected = self.selected.min(self.visible().len().saturating_sub(1));
+    }
+}
+
+fn edit_line(line: &mut String, code: KeyCode) {
+    match code {
+        KeyCode::Char(c) => line.push(c),
+        KeyCode::Backspace => {
+            line.pop();
+        }
+        _ => {}
+    }
+}
+
+/// Run the interface on the journal at `path` until the user quits, waiting
+/// up to `lock_timeout` for the lock whenever it loads or changes the journal.
+pub fn run(storage: &mut dyn Storage, path: &Path, lock_timeout: Duration, history_depth: usize) -> Result<()> {
+    let journal = {
+        let _lock = JournalLock::acquire(path, lock_timeout)?;
+        storage.load()?
+    };
+    let mut app = App::new(journal, history_depth, Local::now().date_naive());
+    app.lock = Some((path.to_path_buf(), lock_timeout));
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app, storage);
+    ratatui::try_restore()?;
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, storage: &mut dyn Storage) -> Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| render(app, frame))?;
+        if !event::poll(REFRESH_INTERVAL)? {
+            app.refresh(storage);
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(id) = app.handle_key(key, storage) {
+            // Like the `edit` command, hold the lock until the editor closes.
+            let lock = match app.lock(true) {
+                Ok(lock) => lock,
+                Err(e) => {
+                    app.message = Some(Message::Error(e.to_string()));
+                    continue;
+                }
+            };
+            // The editor needs the terminal to itself.
+            ratatui::try_restore()?;
+            let mut recorder = Recorder::new(storage, "edit", app.history_depth);
+            let result = tasks::edit_tasks(&mut recorder, Some(id));
+            *terminal = ratatui::try_init()?;
+            terminal.clear()?;
+            app.reload(storage);
+            drop(lock);
+            app.message = Some(match result {
+                Ok(()) => Message::Info(format!("Edited task {}.", id)),
+                Err(e) => Message::Error(e.to_string()),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Draw the whole interface.
+pub fn render(app: &App, frame: &mut Frame) {
+    let [list, status, help] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(1), Constraint::Length(1)]).areas(frame.area());
+
+    let tasks = app.visible();
+    let open = app.journal.open_ids();
+    let rows: Vec<Row> = tasks
+        .iter()
+        .map(|task| {
+            let indent = if task.parent.is_some_and(|parent| tasks.iter().any(|task| task.id == parent)) {
+                "  "
+            } else {
+                ""
+            };
+            let mut text = format!("{}{}", indent, task.text);
+            if task.is_blocked(&open) && !task.is_completed() {
+                text += " (blocked)";
+            }
+            let due = task.due.map(|due| due.format(&config::appearance().date_format).to_string());
+            let due = Cell::from(due.unwrap_or_default())
+                .style(colour(output::due_colour(task, app.today)));
+            let priority = Cell::from(task.priority.map(|priority| priority.to_string()).unwrap_or_default())
+                .style(colour(task.priority.and_then(output::priority_colour)));
+            let tags: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+            let row = Row::new([
+                Cell::from(task.id.to_string()),
+                Cell::from(text),
+                due,
+                priority,
+                Cell::from(task.project.clone().unwrap_or_default()),
+                Cell::from(tags.join(" ")),
+            ]);
+            if task.is_completed() {
+                row.dark_gray().crossed_out()
+            } else {
+                row
+            }
+        })
+        .collect();
+
+    let mut title = format!(" rusty-journal: {} shown ", tasks.len());
+    if !app.filter.is_empty() {
+        title += &format!("- {} ", app.filter);
+    }
+    if app.options.show == Show::All {
+        title += "- with completed ";
+    }
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Fill(1),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(12),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["ID", "Task", "Due", "Priority", "Project", "Tags"]).bold())
+        .block(Block::bordered().title(title))
+        .row_highlight_style(Style::new().reversed())
+        .highlight_symbol("> ");
+    let mut state = TableState::default().with_selected((!tasks.is_empty()).then_some(app.selected));
+    frame.render_stateful_widget(table, list, &mut state);
+
+    let line = match (&app.mode, &app.message) {
+        (Mode::Filter(query), message) => {
+            let mut line = Line::from(vec![Span::from("Filter: ").bold(), Span::from(query.as_str())]);
+            if let Some(Message::Error(error)) = message {
+                line.push_span(Span::from(format!("  {}", error)).red());
+            }
+            line
+        }
+        (Mode::Add { text, parent }, _) => {
+            let prompt = match parent {
+                Some(parent) => format!("New subtask of #{}: ", parent),
+                None => "New task: ".to_string(),
+            };
+            Line::from(vec![Span::from(prompt).bold(), Span::from(text.as_str())])
+        }
+        (Mode::Browse, Some(Message::Info(info))) => Line::from(info.as_str()),
+        (Mode::Browse, Some(Message::Error(error))) => Line::from(error.as_str()).red(),
+        (Mode::Browse, None) => Line::default(),
+    };
+    frame.render_widget(Paragraph::new(line), status);
+    frame.render_widget(Paragraph::new(HELP).dark_gray(), help);
+}
+
+fn colour(colour: Option<table::Colour>) -> Style {
+    match colour {
+        Some(table::Colour::Red) => Style::new().fg(Color::Red),
+        Some(table::Colour::Yellow) => Style::new().fg(Color::Yellow),
+        Some(table::Colour::Green) => Style::new().fg(Color::Green),
+        Some(table::Colour::Blue) => Style::new().fg(Color::Blue),
+        Some(table::Colour::Magenta) => Style::new().fg(Color::Magenta),
+        Some(table::Colour::Cyan) => Style::new().fg(Color::Cyan),
+        Some(table::Colour::Dim) => Style::new().dim(),
+        Some(table::Colour::Bold) => Style::new().bold(),
+        Some(table::Colour::Highlight) => Style::new().bold().underlined(),
+        None => Style::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 18).unwrap()
+    }
+
+    fn setup() -> (App, MemoryStore) {
+        let mut storage = MemoryStore::default();
+        let mut journal = Journal::default();
+        let mut report = Task::new("Write the quarterly report".to_string());
+        report.due = NaiveDate::from_ymd_opt(2026, 10, 20);
+        report.priority = Some(tasks::Priority::High);
+        report.project = Some("work".to_string());
+        journal.push(report);
+        let mut numbers = Task::new("Gather the numbers".to_string());
+        numbers.parent = Some(1);
+        numbers.tags.insert("finance".to_string());
+        journal.push(numbers);
+        journal.push(Task::new("Reunião com a equipe 🎉".to_string()));
+        storage.save(&journal).unwrap();
+        (App::new(journal, 10, today()), storage)
+    }
+
+    fn draw(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(80, 9)).unwrap();
+        terminal.draw(|frame| render(app, frame)).unwrap();
+        terminal.backend().to_string()
+    }
+
+    fn press(app: &mut App, storage: &mut MemoryStore, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            app.handle_key(KeyEvent::new(code, KeyModifiers::NONE), storage);
+        }
+    }
+
+    #[test]
+    fn lists_tasks() {
+        let (app, _) = setup();
+        insta::assert_snapshot!(draw(&app));
+    }
+
+    #[test]
+    fn adds_and_completes_tasks() {
+        let (mut app, mut storage) = setup();
+        press(&mut app, &mut storage, "GaBuy milk");
+        insta::assert_snapshot!("typing_a_task", draw(&app));
+
+        press(&mut app, &mut storage, "\n");
+        assert_eq!(storage.load().unwrap().tasks.len(), 4);
+        assert_eq!(app.selected_id(), Some(4));
+
+        // The report has an open subtask, so only `D` completes it.
+        press(&mut app, &mut storage, "gd");
+        insta::assert_snapshot!("refused_completion", draw(&app));
+        press(&mut app, &mut storage, "D");
+        let journal = storage.load().unwrap();
+        assert!(journal.tasks[0].is_completed() && journal.tasks[1].is_completed());
+        assert_eq!(storage.load_history().unwrap().done.len(), 2);
+
+        press(&mut app, &mut storage, "c");
+        insta::assert_snapshot!("with_completed", draw(&app));
+    }
+
+    #[test]
+    fn locks_the_journal_only_for_changes() {
+        let (mut app, mut storage) = setup();
+        let path = std::env::temp_dir().join(format!("rusty-journal-tui-{}.json", std::process::id()));
+        app.lock = Some((path.clone(), Duration::ZERO));
+
+        // While another command has the journal, changes wait for it.
+        let held = JournalLock::acquire(&path, Duration::ZERO).unwrap();
+        press(&mut app, &mut storage, "Gd");
+        assert!(matches!(app.message, Some(Message::Error(_))));
+        assert!(!storage.load().unwrap().tasks[2].is_completed());
+
+        // Once it's done, the interface sees its changes.
+        let mut journal = storage.load().unwrap();
+        journal.push(Task::new("Added from another shell".to_string()));
+        storage.save(&journal).unwrap();
+        drop(held);
+        app.refresh(&mut storage);
+        assert_eq!(app.journal.tasks.len(), 4);
+        press(&mut app, &mut storage, "gjjd");
+        assert!(storage.load().unwrap().tasks[2].is_completed());
+        let _ = std::fs::remove_file(path.with_extension("json.lock"));
+    }
+
+    #[test]
+    fn filters_with_the_query_language() {
+        let (mut app, mut storage) = setup();
+        press(&mut app, &mut storage, "/project:work or +finance\n");
+        assert_eq!(app.visible().len(), 2);
+
+        press(&mut app, &mut storage, "/(\n");
+        insta::assert_snapshot!("invalid_filter", draw(&app));
+        press(&mut app, &mut storage, "\x1b");
+        assert_eq!(app.filter, "project:work or +finance");
+    }
+}